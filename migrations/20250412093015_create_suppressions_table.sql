@@ -0,0 +1,8 @@
+-- create suppressions table
+CREATE TABLE suppressions (
+    email TEXT NOT NULL,
+    PRIMARY KEY (email),
+    reason TEXT NOT NULL,
+    source TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
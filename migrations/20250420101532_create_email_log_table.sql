@@ -0,0 +1,14 @@
+-- create email_log table
+CREATE TABLE email_log (
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    recipient TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    issue_id uuid NULL,
+    provider_message_id TEXT NULL,
+    status TEXT NOT NULL,
+    latency_millis INTEGER NOT NULL,
+    error TEXT NULL,
+    sent_at timestamptz NOT NULL
+);
+CREATE INDEX email_log_recipient_sent_at_idx ON email_log (recipient, sent_at DESC);
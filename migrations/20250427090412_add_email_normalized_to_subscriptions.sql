@@ -0,0 +1,33 @@
+-- add the normalized email address to subscriptions and make it unique.
+-- existing rows are lowercased only, IDN domains are converted when they subscribe again.
+BEGIN;
+    ALTER TABLE subscriptions ADD COLUMN email_normalized TEXT NULL;
+    UPDATE subscriptions SET email_normalized = lower(trim(email));
+
+    -- merge subscribers saved once per case variant of their address, keeping the
+    -- confirmed one or else the oldest, along with the tokens and deliveries of the others
+    CREATE TEMPORARY TABLE subscription_duplicates ON COMMIT DROP AS
+    SELECT id, email,
+        first_value(id) OVER kept AS kept_id,
+        first_value(email) OVER kept AS kept_email
+    FROM subscriptions
+    WINDOW kept AS (
+        PARTITION BY email_normalized
+        ORDER BY status = 'confirmed' DESC, subscribed_at, id
+    );
+    DELETE FROM subscription_duplicates WHERE id = kept_id;
+    UPDATE subscription_tokens t SET subscriber_id = d.kept_id
+    FROM subscription_duplicates d WHERE t.subscriber_id = d.id;
+    UPDATE email_log l SET recipient = d.kept_email
+    FROM subscription_duplicates d WHERE l.recipient = d.email;
+    DELETE FROM subscriptions s USING subscription_duplicates d WHERE s.id = d.id;
+
+    ALTER TABLE subscriptions ALTER COLUMN email_normalized SET NOT NULL;
+    ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_email_normalized_key UNIQUE (email_normalized);
+
+    -- key suppressions by the normalized address, keeping the oldest of duplicates
+    DELETE FROM suppressions a USING suppressions b
+    WHERE lower(trim(a.email)) = lower(trim(b.email))
+    AND (a.created_at, a.email) > (b.created_at, b.email);
+    UPDATE suppressions SET email = lower(trim(email));
+COMMIT;
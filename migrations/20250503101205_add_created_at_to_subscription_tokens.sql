@@ -0,0 +1,2 @@
+-- record when tokens are issued so that confirmation links can expire
+ALTER TABLE subscription_tokens ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
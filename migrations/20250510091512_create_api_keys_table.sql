@@ -0,0 +1,14 @@
+-- create api_keys table
+CREATE TABLE api_keys (
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    name TEXT NOT NULL,
+    -- first characters of the key, to tell keys apart without storing them
+    key_prefix TEXT NOT NULL,
+    -- SHA-256 of the key, the key itself is only shown on creation
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created_at timestamptz NOT NULL,
+    last_used_at timestamptz,
+    revoked_at timestamptz
+);
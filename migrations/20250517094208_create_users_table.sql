@@ -0,0 +1,11 @@
+-- create users table
+CREATE TABLE users (
+    user_id uuid NOT NULL,
+    PRIMARY KEY (user_id),
+    username TEXT NOT NULL UNIQUE,
+    -- Argon2id PHC string, passwords are never stored in clear
+    password_hash TEXT NOT NULL,
+    -- what the user may do on the admin routes, see `Role`
+    role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'analyst', 'support')),
+    created_at timestamptz NOT NULL
+);
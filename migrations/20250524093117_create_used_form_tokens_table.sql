@@ -0,0 +1,9 @@
+-- create used_form_tokens table
+CREATE TABLE used_form_tokens (
+    -- random part of a sign-up form token, each token is accepted once
+    nonce TEXT NOT NULL,
+    PRIMARY KEY (nonce),
+    -- rows of expired tokens are purged, those are rejected anyway
+    issued_at timestamptz NOT NULL
+);
+CREATE INDEX used_form_tokens_issued_at_idx ON used_form_tokens (issued_at);
@@ -0,0 +1,9 @@
+-- add the normalized recipient to email_log, so that filtering ignores the case of the address.
+-- recipients were logged with their domain converted already, lowercasing them is enough.
+BEGIN;
+    ALTER TABLE email_log ADD COLUMN recipient_normalized TEXT NULL;
+    UPDATE email_log SET recipient_normalized = lower(trim(recipient));
+    ALTER TABLE email_log ALTER COLUMN recipient_normalized SET NOT NULL;
+    DROP INDEX email_log_recipient_sent_at_idx;
+    CREATE INDEX email_log_recipient_normalized_sent_at_idx ON email_log (recipient_normalized, sent_at DESC);
+COMMIT;
@@ -0,0 +1,196 @@
+use chrono::{DateTime, Utc};
+use rand::{distr::Alphanumeric, rng, Rng};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::router::DbPool;
+
+/// Prefix of every API key, to recognize them in logs and secret scanners
+const KEY_PREFIX: &str = "nl_";
+
+/// Number of random alphanumeric characters of a key
+const KEY_LENGTH: usize = 40;
+
+/// Number of characters of a key kept in clear to tell keys apart
+const DISPLAYED_LENGTH: usize = KEY_PREFIX.len() + 8;
+
+/// Operation an API key is allowed to perform
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    NewslettersPublish,
+    SubscribersRead,
+}
+
+impl Scope {
+    /// Returns the scope as stored and sent to clients
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NewslettersPublish => "newsletters:publish",
+            Self::SubscribersRead => "subscribers:read",
+        }
+    }
+
+    /// Parses a scope, returning None if it is unknown
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "newsletters:publish" => Some(Self::NewslettersPublish),
+            "subscribers:read" => Some(Self::SubscribersRead),
+            _ => None,
+        }
+    }
+}
+
+/// An API key as listed to administrators, without the key itself
+#[derive(Serialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// The key a request authenticated with
+pub struct AuthenticatedKey {
+    pub id: Uuid,
+    pub scopes: Vec<String>,
+}
+
+impl AuthenticatedKey {
+    /// Tells whether the key was granted a scope
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.iter().any(|s| s == scope.as_str())
+    }
+}
+
+/// Returns the hash a key is stored and looked up by
+///
+/// Keys are long random strings, a fast hash is enough to protect them at rest.
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn generate_key() -> String {
+    let mut rng = rng();
+    let secret: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(KEY_LENGTH)
+        .collect();
+    format!("{}{}", KEY_PREFIX, secret)
+}
+
+/// Creates a new API key
+///
+/// # Arguments
+/// * `pool` - Database pool
+/// * `name` - Name telling administrators what the key is used for
+/// * `scopes` - Operations the key is allowed to perform
+///
+/// # Returns
+/// The stored key along with the key itself, which can't be retrieved afterwards
+#[instrument(name = "Create an API key", skip(pool))]
+pub async fn create_api_key(
+    pool: &DbPool,
+    name: &str,
+    scopes: &[Scope],
+) -> Result<(ApiKey, String), sqlx::Error> {
+    let key = generate_key();
+    let scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_string()).collect();
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        r#"INSERT INTO api_keys (id, name, key_prefix, key_hash, scopes, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, name, key_prefix, scopes, created_at, last_used_at, revoked_at"#,
+        Uuid::new_v4(),
+        name,
+        &key[..DISPLAYED_LENGTH],
+        hash_key(&key),
+        &scopes,
+        Utc::now()
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((api_key, key))
+}
+
+/// Lists every API key, most recent first
+#[instrument(name = "List API keys", skip_all)]
+pub async fn list_api_keys(pool: &DbPool) -> Result<Vec<ApiKey>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKey,
+        r#"SELECT id, name, key_prefix, scopes, created_at, last_used_at, revoked_at
+        FROM api_keys ORDER BY created_at DESC"#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Revokes an API key, it can't authenticate requests anymore
+///
+/// # Returns
+/// true if the key existed and was not revoked already
+#[instrument(name = "Revoke an API key", skip(pool))]
+pub async fn revoke_api_key(pool: &DbPool, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE api_keys SET revoked_at = $2 WHERE id = $1 AND revoked_at IS NULL"#,
+        id,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Looks up an unrevoked API key, recording that it was used
+///
+/// # Returns
+/// The key if it exists and was not revoked, None otherwise
+#[instrument(name = "Authenticate an API key", skip_all)]
+pub async fn authenticate(
+    pool: &DbPool,
+    key: &str,
+) -> Result<Option<AuthenticatedKey>, sqlx::Error> {
+    sqlx::query_as!(
+        AuthenticatedKey,
+        r#"UPDATE api_keys SET last_used_at = $2
+        WHERE key_hash = $1 AND revoked_at IS NULL
+        RETURNING id, scopes"#,
+        hash_key(key),
+        Utc::now()
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_round_trip_through_their_string_form() {
+        for scope in [Scope::NewslettersPublish, Scope::SubscribersRead] {
+            assert_eq!(Scope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(Scope::parse("newsletters:delete"), None);
+    }
+
+    #[test]
+    fn generated_keys_are_unique_and_prefixed() {
+        let (first, second) = (generate_key(), generate_key());
+        assert_ne!(first, second);
+        assert!(first.starts_with(KEY_PREFIX));
+        assert_eq!(first.len(), KEY_PREFIX.len() + KEY_LENGTH);
+    }
+
+    #[test]
+    fn keys_are_not_stored_in_clear() {
+        let key = generate_key();
+        let hash = hash_key(&key);
+        assert_ne!(hash, key);
+        assert_eq!(hash, hash_key(&key));
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::Context;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use secrecy::{ExposeSecret, SecretString};
+use subtle::ConstantTimeEq;
+use tracing::{error, warn};
+
+use crate::{
+    api_keys::{self, Scope},
+    configuration::BasicAuthSettings,
+    error_code::ErrorCode,
+    router::{AppState, ErrorResponse},
+    users::{self, AdminUser, Permission, Role},
+    utils::error_chain_fmt,
+};
+
+/// Credentials extracted from a `Basic` authorization header
+pub struct Credentials {
+    pub username: String,
+    pub password: SecretString,
+}
+
+impl Credentials {
+    /// Checks the credentials against the configured ones
+    ///
+    /// Both are compared in constant time, so that response times don't leak them.
+    ///
+    /// # Returns
+    /// Ok(()) if they match, Error otherwise
+    pub fn validate(&self, expected: &BasicAuthSettings) -> Result<(), anyhow::Error> {
+        let username = self.username.as_bytes().ct_eq(expected.username.as_bytes());
+        let password = self
+            .password
+            .expose_secret()
+            .as_bytes()
+            .ct_eq(expected.password.expose_secret().as_bytes());
+        if !bool::from(username & password) {
+            anyhow::bail!("Invalid username or password.");
+        }
+        Ok(())
+    }
+}
+
+/// Extracts the credentials of a `Basic` authorization header
+///
+/// # Arguments
+/// * `headers` - Request headers
+///
+/// # Returns
+/// The decoded credentials if successful, Error otherwise
+pub fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, anyhow::Error> {
+    let header_value = headers
+        .get(header::AUTHORIZATION)
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string")?;
+    let base64encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'.")?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64encoded_segment)
+        .context("Failed to base64-decode 'Basic' credentials.")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8.")?;
+
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .context("The 'Basic' credentials are not in the 'username:password' format.")?;
+    Ok(Credentials {
+        username: username.to_string(),
+        password: SecretString::from(password),
+    })
+}
+
+#[derive(thiserror::Error)]
+pub enum AuthError {
+    #[error("Authentication failed.")]
+    InvalidCredentials(#[source] anyhow::Error),
+    #[error("Your role does not grant the {} permission.", .0.as_str())]
+    MissingPermission(Permission),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = match self {
+            Self::InvalidCredentials(_) => (StatusCode::UNAUTHORIZED, ErrorCode::Unauthorized),
+            Self::MissingPermission(_) => (StatusCode::FORBIDDEN, ErrorCode::Forbidden),
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
+        };
+
+        // Create the error response body
+        let body = ErrorResponse::new(status_code, code, self.to_string());
+
+        // Log the error
+        match &self {
+            Self::InvalidCredentials(_) | Self::MissingPermission(_) => warn!("{:?}", self),
+            Self::UnexpectedError(e) => error!("{:?}", e),
+        }
+
+        let mut response = body.into_response();
+        if let Self::InvalidCredentials(_) = self {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(r#"Basic realm="admin""#),
+            );
+        }
+        response
+    }
+}
+
+/// Middleware rejecting requests without valid admin credentials
+///
+/// The bootstrap owner credentials, when configured, sign in as an owner so that the first
+/// users can be created. Otherwise the credentials are checked against the users table.
+/// The authenticated [`AdminUser`] is added to the request extensions.
+pub(crate) async fn require_admin(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AuthError> {
+    let credentials =
+        basic_authentication(request.headers()).map_err(AuthError::InvalidCredentials)?;
+    let bootstrap_owner = state
+        .admin
+        .as_deref()
+        .is_some_and(|admin| credentials.validate(admin).is_ok());
+    let user = if bootstrap_owner {
+        AdminUser {
+            username: credentials.username,
+            role: Role::Owner,
+        }
+    } else {
+        users::authenticate(&state.db, &credentials.username, credentials.password)
+            .await?
+            .ok_or_else(|| {
+                AuthError::InvalidCredentials(anyhow::anyhow!("Invalid username or password."))
+            })?
+    };
+    request.extensions_mut().insert(user);
+    Ok(next.run(request).await)
+}
+
+/// Middleware rejecting admin users whose role doesn't grant the permission of the route
+///
+/// Must run behind [`require_admin`], which authenticates the user.
+pub(crate) async fn require_permission(
+    State(permission): State<Permission>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AuthError> {
+    let user = request
+        .extensions()
+        .get::<AdminUser>()
+        .context("The admin user was not authenticated.")?;
+    if !user.role.has_permission(permission) {
+        return Err(AuthError::MissingPermission(permission));
+    }
+    Ok(next.run(request).await)
+}
+
+/// Extracts the token of a `Bearer` authorization header
+///
+/// # Arguments
+/// * `headers` - Request headers
+///
+/// # Returns
+/// The token if successful, Error otherwise
+pub fn bearer_token(headers: &HeaderMap) -> Result<&str, anyhow::Error> {
+    headers
+        .get(header::AUTHORIZATION)
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string")?
+        .strip_prefix("Bearer ")
+        .context("The authorization scheme was not 'Bearer'.")
+}
+
+#[derive(thiserror::Error)]
+pub enum ApiKeyError {
+    #[error("Authentication failed.")]
+    Unauthorized(#[source] anyhow::Error),
+    #[error("The API key was not granted the {} scope.", .0.as_str())]
+    MissingScope(Scope),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ApiKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for ApiKeyError {
+    fn into_response(self) -> Response {
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = match self {
+            Self::Unauthorized(_) => (StatusCode::UNAUTHORIZED, ErrorCode::Unauthorized),
+            Self::MissingScope(_) => (StatusCode::FORBIDDEN, ErrorCode::Forbidden),
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
+        };
+
+        // Create the error response body
+        let body = ErrorResponse::new(status_code, code, self.to_string());
+
+        // Log the error
+        match &self {
+            Self::Unauthorized(_) | Self::MissingScope(_) => warn!("{:?}", self),
+            Self::UnexpectedError(e) => error!("{:?}", e),
+        }
+
+        let mut response = body.into_response();
+        if let Self::Unauthorized(_) = self {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+/// Middleware rejecting requests without an API key granted the scope of the route
+///
+/// Every accepted key has its last use recorded.
+pub(crate) async fn require_scope(
+    State((state, scope)): State<(AppState, Scope)>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiKeyError> {
+    let key = bearer_token(request.headers()).map_err(ApiKeyError::Unauthorized)?;
+    let api_key = api_keys::authenticate(&state.db, key)
+        .await
+        .context("Failed to look up the API key.")?
+        .ok_or_else(|| ApiKeyError::Unauthorized(anyhow::anyhow!("Unknown or revoked API key.")))?;
+    if !api_key.has_scope(scope) {
+        return Err(ApiKeyError::MissingScope(scope));
+    }
+    Ok(next.run(request).await)
+}
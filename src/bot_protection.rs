@@ -0,0 +1,329 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::{rng, Rng};
+use reqwest::{Client, Url};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::Sha256;
+use std::time::Duration;
+
+use crate::{configuration::BotProtectionSettings, router::DbPool, utils::error_chain_fmt};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long a form token stays valid after being issued
+const FORM_TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Challenge answers submitted along with the sign-up form
+pub struct Challenge<'a> {
+    /// Value of the hidden honeypot field, humans leave it empty
+    pub honeypot: &'a str,
+    /// Signed timestamp and nonce issued when the form was rendered
+    pub form_token: Option<&'a str>,
+    /// hCaptcha or Turnstile response token
+    pub captcha_response: Option<&'a str>,
+}
+
+#[derive(thiserror::Error)]
+pub enum ChallengeError {
+    #[error("{0}")]
+    Rejected(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ChallengeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Verifies that sign-ups come from humans
+pub struct BotProtection {
+    honeypot: bool,
+    min_submit_time: Duration,
+    signing_key: SecretString,
+    captcha: Option<CaptchaVerifier>,
+}
+
+impl BotProtection {
+    /// Creates a new bot protection from configuration settings
+    ///
+    /// # Returns
+    /// The bot protection, or an error if the captcha verification URL is invalid or the
+    /// HTTP client can't be built
+    pub fn new(conf: &BotProtectionSettings) -> Result<Self, anyhow::Error> {
+        let captcha = match &conf.captcha {
+            Some(c) => Some(CaptchaVerifier {
+                http_client: Client::builder()
+                    .timeout(c.timeout())
+                    .build()
+                    .context("Failed to build the captcha HTTP client")?,
+                verify_url: Url::parse(&c.verify_url)
+                    .context("Invalid captcha verification URL")?,
+                secret: c.secret.clone(),
+            }),
+            None => None,
+        };
+        Ok(Self {
+            honeypot: conf.honeypot,
+            min_submit_time: conf.min_submit_time(),
+            signing_key: conf.signing_key.clone(),
+            captcha,
+        })
+    }
+
+    /// Issues a single-use form token signing the current time
+    pub fn issue_form_token(&self) -> String {
+        let nonce: [u8; 16] = rng().random();
+        self.sign_form_token(Utc::now().timestamp(), &hex::encode(nonce))
+    }
+
+    /// Runs every enabled check against the submitted challenge
+    ///
+    /// # Arguments
+    /// * `pool` - Database pool, recording the form tokens used already
+    /// * `challenge` - Answers submitted along with the form
+    ///
+    /// # Returns
+    /// Ok(()) if the submission looks human, ChallengeError otherwise
+    pub async fn verify(
+        &self,
+        pool: &DbPool,
+        challenge: &Challenge<'_>,
+    ) -> Result<(), ChallengeError> {
+        if self.honeypot && !challenge.honeypot.is_empty() {
+            return Err(ChallengeError::Rejected(
+                "The submission was flagged as automated.".to_string(),
+            ));
+        }
+
+        let form_token = if self.min_submit_time.is_zero() {
+            None
+        } else {
+            let form_token = challenge.form_token.ok_or_else(|| {
+                ChallengeError::Rejected("The form token is missing.".to_string())
+            })?;
+            Some(self.verify_form_token(form_token, Utc::now().timestamp())?)
+        };
+
+        if let Some(captcha) = &self.captcha {
+            let response = challenge.captcha_response.ok_or_else(|| {
+                ChallengeError::Rejected("The captcha response is missing.".to_string())
+            })?;
+            captcha.verify(response).await?;
+        }
+
+        // Spent last, so that a failed captcha doesn't cost the visitor their token
+        if let Some(form_token) = form_token {
+            use_form_token(pool, &form_token).await?;
+        }
+
+        Ok(())
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(self.signing_key.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size")
+    }
+
+    fn sign_form_token(&self, issued_at: i64, nonce: &str) -> String {
+        let payload = format!("{}.{}", issued_at, nonce);
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        format!("{}.{}", payload, hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// Checks the signature and age of a form token
+    ///
+    /// # Returns
+    /// The verified token, still to be spent
+    fn verify_form_token(&self, form_token: &str, now: i64) -> Result<FormToken, ChallengeError> {
+        let invalid = || ChallengeError::Rejected("The form token is invalid.".to_string());
+
+        let (payload, signature) = form_token.rsplit_once('.').ok_or_else(invalid)?;
+        let signature = hex::decode(signature).map_err(|_| invalid())?;
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| invalid())?;
+
+        let (issued_at, nonce) = payload.split_once('.').ok_or_else(invalid)?;
+        let issued_at: i64 = issued_at.parse().map_err(|_| invalid())?;
+        let elapsed = u64::try_from(now - issued_at).map_err(|_| invalid())?;
+        if elapsed > FORM_TOKEN_TTL.as_secs() {
+            return Err(ChallengeError::Rejected(
+                "The form token has expired, reload the form.".to_string(),
+            ));
+        }
+        if elapsed < self.min_submit_time.as_secs() {
+            return Err(ChallengeError::Rejected(
+                "The form was submitted too quickly.".to_string(),
+            ));
+        }
+        Ok(FormToken {
+            issued_at: DateTime::from_timestamp(issued_at, 0).ok_or_else(invalid)?,
+            nonce: nonce.to_string(),
+        })
+    }
+}
+
+/// A form token whose signature and age were verified
+#[derive(Debug)]
+struct FormToken {
+    issued_at: DateTime<Utc>,
+    nonce: String,
+}
+
+/// Records a form token as used, purging those that expired
+///
+/// # Returns
+/// Ok(()) the first time a token is used, ChallengeError::Rejected afterwards
+async fn use_form_token(pool: &DbPool, form_token: &FormToken) -> Result<(), ChallengeError> {
+    let expired_before = Utc::now() - FORM_TOKEN_TTL;
+    sqlx::query!(
+        r#"DELETE FROM used_form_tokens WHERE issued_at < $1"#,
+        expired_before
+    )
+    .execute(pool)
+    .await
+    .context("Failed to purge the expired form tokens.")?;
+
+    let result = sqlx::query!(
+        r#"INSERT INTO used_form_tokens (nonce, issued_at) VALUES ($1, $2)
+        ON CONFLICT (nonce) DO NOTHING"#,
+        form_token.nonce,
+        form_token.issued_at
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record the form token as used.")?;
+    if result.rows_affected() == 0 {
+        return Err(ChallengeError::Rejected(
+            "The form token was used already, reload the form.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Client for an hCaptcha or Turnstile `siteverify` endpoint
+struct CaptchaVerifier {
+    http_client: Client,
+    verify_url: Url,
+    secret: SecretString,
+}
+
+#[derive(serde::Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+}
+
+impl CaptchaVerifier {
+    async fn verify(&self, response: &str) -> Result<(), ChallengeError> {
+        let outcome: SiteVerifyResponse = self
+            .http_client
+            .post(self.verify_url.clone())
+            .form(&[
+                ("secret", self.secret.expose_secret()),
+                ("response", response),
+            ])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("Failed to call the captcha verification endpoint.")?
+            .json()
+            .await
+            .context("Failed to parse the captcha verification response.")?;
+
+        if !outcome.success {
+            return Err(ChallengeError::Rejected(
+                "The captcha verification failed.".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::CaptchaSettings;
+    use claims::{assert_err, assert_ok};
+
+    fn bot_protection(min_submit_secs: u64) -> BotProtection {
+        BotProtection::new(&BotProtectionSettings {
+            honeypot: true,
+            min_submit_secs,
+            signing_key: SecretString::from("signing-key"),
+            captcha: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn a_form_token_submitted_after_the_minimum_time_is_accepted() {
+        let protection = bot_protection(5);
+        let token = protection.sign_form_token(1_000, "nonce");
+        assert_ok!(protection.verify_form_token(&token, 1_005));
+    }
+
+    #[test]
+    fn a_form_token_submitted_too_quickly_is_rejected() {
+        let protection = bot_protection(5);
+        let token = protection.sign_form_token(1_000, "nonce");
+        assert_err!(protection.verify_form_token(&token, 1_004));
+    }
+
+    #[test]
+    fn an_expired_form_token_is_rejected() {
+        let protection = bot_protection(5);
+        let token = protection.sign_form_token(1_000, "nonce");
+        let now = 1_000 + FORM_TOKEN_TTL.as_secs() as i64 + 1;
+        assert_err!(protection.verify_form_token(&token, now));
+    }
+
+    #[test]
+    fn a_tampered_form_token_is_rejected() {
+        let protection = bot_protection(5);
+        let token = protection.sign_form_token(1_000, "nonce");
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = format!("900.{}", signature);
+        assert_err!(protection.verify_form_token(&forged, 1_005));
+    }
+
+    #[test]
+    fn a_form_token_without_a_nonce_is_rejected() {
+        let protection = bot_protection(5);
+        let mut mac = protection.mac();
+        mac.update(b"1000");
+        let token = format!("1000.{}", hex::encode(mac.finalize().into_bytes()));
+        assert_err!(protection.verify_form_token(&token, 1_005));
+    }
+
+    #[test]
+    fn an_invalid_captcha_verification_url_is_reported() {
+        let result = BotProtection::new(&BotProtectionSettings {
+            honeypot: true,
+            min_submit_secs: 5,
+            signing_key: SecretString::from("signing-key"),
+            captcha: Some(CaptchaSettings {
+                verify_url: "not a url".to_string(),
+                secret: SecretString::from("secret"),
+                timeout_millis: 2000,
+            }),
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn a_form_token_signed_with_another_key_is_rejected() {
+        let token = BotProtection::new(&BotProtectionSettings {
+            honeypot: true,
+            min_submit_secs: 5,
+            signing_key: SecretString::from("another-key"),
+            captcha: None,
+        })
+        .unwrap()
+        .sign_form_token(1_000, "nonce");
+        assert_err!(bot_protection(5).verify_form_token(&token, 1_005));
+    }
+}
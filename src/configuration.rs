@@ -1,4 +1,5 @@
 use anyhow::Context;
+use chrono::{DateTime, Utc};
 use config::{Config, ConfigError, File};
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
@@ -7,9 +8,14 @@ use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions, PgSslMode},
     PgPool,
 };
-use std::{path::PathBuf, time::Duration};
+use std::{
+    net::SocketAddr,
+    num::{NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    time::Duration,
+};
 
-use crate::domain::SubscriberEmail;
+use crate::domain::Mailbox;
 
 /// Main application settings structure
 #[derive(Deserialize, Clone)]
@@ -18,6 +24,18 @@ pub struct Settings {
     pub server: ServerSettings,
     pub logs: Option<LogsSettings>,
     pub email_client: EmailClientSettings,
+    /// Credentials the email provider calls the webhooks with, disabled unless configured
+    pub webhooks: Option<BasicAuthSettings>,
+    /// Bootstrap owner of the admin routes, disabled unless configured
+    pub admin: Option<BasicAuthSettings>,
+    pub bot_protection: BotProtectionSettings,
+    pub email_domains: EmailDomainSettings,
+    pub confirmation: ConfirmationSettings,
+    #[serde(default)]
+    pub templates: TemplateSettings,
+    pub subscribe_form: SubscribeFormSettings,
+    #[serde(default)]
+    pub api_docs: ApiDocsSettings,
 }
 
 /// HTTP server configuration settings
@@ -27,6 +45,41 @@ pub struct ServerSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub base_url: String,
+    pub rate_limit: RateLimitSettings,
+    pub cors: CorsSettings,
+    pub legacy_routes: LegacyRoutesSettings,
+}
+
+/// Deprecation of the unversioned paths kept as aliases of `/api/v1`
+#[derive(Deserialize, Clone)]
+pub struct LegacyRoutesSettings {
+    pub deprecated_at: DateTime<Utc>,
+    /// Date after which the aliases may be removed
+    pub sunset_at: DateTime<Utc>,
+}
+
+/// Cross-origin access to the public routes from browsers
+#[derive(Deserialize, Clone)]
+pub struct CorsSettings {
+    /// Origins allowed to call the public routes, `*` allows any, empty refuses all
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// Number of seconds browsers may cache a preflight response
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_age_secs: u64,
+}
+
+/// Rate limiting settings for the public endpoints
+#[derive(Deserialize, Clone)]
+pub struct RateLimitSettings {
+    /// Number of reverse proxies whose `X-Forwarded-For` entries are trusted
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub trusted_proxy_hops: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub subscriptions_per_ip_per_minute: NonZeroU32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub subscriptions_per_email_per_hour: NonZeroU32,
 }
 
 /// Database connection settings
@@ -52,9 +105,143 @@ pub struct LogsSettings {
 #[derive(Deserialize, Clone)]
 pub struct EmailClientSettings {
     pub base_url: String,
+    /// Sender mailbox, either a bare address or `"Display Name" <address>`
     pub sender_email: String,
+    /// Mailbox replies are sent to, when it differs from the sender
+    pub reply_to: Option<String>,
     pub authorization_token: SecretString,
     pub timeout_millis: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_messages_per_second: NonZeroU32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_concurrent_requests: NonZeroUsize,
+    /// Deliver through an SMTP relay instead of the Postmark API
+    pub smtp: Option<SmtpSettings>,
+    /// Sign every MIME message with DKIM
+    pub dkim: Option<DkimSettings>,
+}
+
+/// SMTP relay settings
+#[derive(Deserialize, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    /// Require STARTTLS, only disable it for a relay on a trusted network
+    pub starttls: bool,
+    pub username: Option<String>,
+    pub password: Option<SecretString>,
+}
+
+/// DKIM signing settings
+#[derive(Deserialize, Clone)]
+pub struct DkimSettings {
+    /// Selector of the public key published at `<selector>._domainkey.<domain>`
+    pub selector: String,
+    /// Signing domain, which the sender must be on for messages to pass DMARC
+    pub domain: String,
+    pub algorithm: DkimAlgorithm,
+    /// PKCS#1 PEM private key for RSA, base64 encoded seed for Ed25519
+    pub private_key: Option<SecretString>,
+    /// File holding the private key, read when `private_key` is not set
+    pub private_key_path: Option<String>,
+}
+
+/// DKIM signing algorithm
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DkimAlgorithm {
+    Rsa,
+    Ed25519,
+}
+
+/// Bot protection settings for the sign-up form
+#[derive(Deserialize, Clone)]
+pub struct BotProtectionSettings {
+    /// Reject submissions filling in the hidden honeypot field
+    pub honeypot: bool,
+    /// Minimum number of seconds between issuing a form token and submitting the form,
+    /// 0 disables the check
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_submit_secs: u64,
+    pub signing_key: SecretString,
+    pub captcha: Option<CaptchaSettings>,
+}
+
+/// hCaptcha or Turnstile verification settings
+#[derive(Deserialize, Clone)]
+pub struct CaptchaSettings {
+    pub verify_url: String,
+    pub secret: SecretString,
+    pub timeout_millis: u64,
+}
+
+/// Checks run on the domain of every email signing up
+#[derive(Deserialize, Clone)]
+pub struct EmailDomainSettings {
+    /// Disposable email providers refused at sign-up, their subdomains included
+    #[serde(default)]
+    pub blocklist: Vec<String>,
+    /// File listing more blocked domains, one per line
+    pub blocklist_path: Option<PathBuf>,
+    /// Check that the domain can receive email, disabled when unset
+    pub dns: Option<DnsCheckSettings>,
+}
+
+/// DNS lookup settings for the MX check
+#[derive(Deserialize, Clone)]
+pub struct DnsCheckSettings {
+    /// Nameserver to query as `ip:port`, defaults to the system resolver
+    pub nameserver: Option<SocketAddr>,
+    pub timeout_millis: u64,
+}
+
+/// Subscription confirmation settings
+#[derive(Deserialize, Clone)]
+pub struct ConfirmationSettings {
+    /// Number of hours a confirmation link stays valid
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub token_ttl_hours: u64,
+    /// Pages browsers are redirected to instead of the built-in landing pages
+    #[serde(default)]
+    pub redirects: ConfirmationRedirects,
+}
+
+/// Redirect URL of every confirmation outcome, the built-in page is shown when unset
+#[derive(Deserialize, Clone, Default)]
+pub struct ConfirmationRedirects {
+    pub confirmed: Option<String>,
+    pub already_confirmed: Option<String>,
+    pub expired: Option<String>,
+    pub unknown_token: Option<String>,
+}
+
+/// HTML page templates settings
+#[derive(Deserialize, Clone, Default)]
+pub struct TemplateSettings {
+    /// Directory whose templates replace the built-in ones of the same name
+    pub dir: Option<PathBuf>,
+}
+
+/// Hosted subscription form settings
+#[derive(Deserialize, Clone)]
+pub struct SubscribeFormSettings {
+    /// Key used to sign the CSRF and flash message cookies
+    pub signing_key: SecretString,
+}
+
+/// API documentation settings
+#[derive(Deserialize, Clone, Default)]
+pub struct ApiDocsSettings {
+    /// Serve a Redoc UI at `/docs`, the OpenAPI document is always served
+    pub ui: bool,
+}
+
+/// Basic auth credentials settings
+#[derive(Deserialize, Clone)]
+pub struct BasicAuthSettings {
+    pub username: String,
+    pub password: SecretString,
 }
 
 impl Settings {
@@ -170,13 +357,49 @@ impl ServerSettings {
     }
 }
 
+impl BotProtectionSettings {
+    /// Returns the minimum time to submit the form
+    pub fn min_submit_time(&self) -> Duration {
+        Duration::from_secs(self.min_submit_secs)
+    }
+}
+
+impl CaptchaSettings {
+    /// Returns the timeout duration
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_millis)
+    }
+}
+
+impl ConfirmationSettings {
+    /// Returns how long a confirmation link stays valid
+    pub fn token_ttl(&self) -> Duration {
+        Duration::from_secs(self.token_ttl_hours.saturating_mul(60 * 60))
+    }
+}
+
+impl DnsCheckSettings {
+    /// Returns the timeout duration
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_millis)
+    }
+}
+
 impl EmailClientSettings {
-    /// Parses the sender email address
+    /// Parses the sender mailbox
+    ///
+    /// # Returns
+    /// A valid Mailbox if successful, Error otherwise
+    pub fn sender(&self) -> Result<Mailbox, String> {
+        Mailbox::parse(self.sender_email.clone())
+    }
+
+    /// Parses the reply-to mailbox, if one is configured
     ///
     /// # Returns
-    /// A valid SubscriberEmail if successful, Error otherwise
-    pub fn sender(&self) -> Result<SubscriberEmail, String> {
-        SubscriberEmail::parse(self.sender_email.clone())
+    /// A valid Mailbox or None if successful, Error otherwise
+    pub fn reply_to(&self) -> Result<Option<Mailbox>, String> {
+        self.reply_to.clone().map(Mailbox::parse).transpose()
     }
 
     /// Returns the timeout duration
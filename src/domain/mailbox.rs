@@ -0,0 +1,163 @@
+use crate::domain::SubscriberEmail;
+
+/// An email address with an optional display name, e.g. `"Acme News" <news@acme.io>`
+#[derive(Debug, Clone)]
+pub struct Mailbox {
+    display_name: Option<String>,
+    address: SubscriberEmail,
+}
+
+impl std::fmt::Display for Mailbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.display_name {
+            Some(name) => {
+                let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "\"{}\" <{}>", escaped, self.address)
+            }
+            None => self.address.fmt(f),
+        }
+    }
+}
+
+impl Mailbox {
+    /// Parses an RFC 5322 mailbox into a valid `Mailbox`.
+    ///
+    /// Accepts a bare address (`news@acme.io`) or a display name followed by
+    /// an address in angle brackets, quoted or not (`"Acme News" <news@acme.io>`).
+    ///
+    /// # Arguments
+    /// * `s` - The string to be parsed as a mailbox
+    ///
+    /// # Returns
+    /// * `Ok(Mailbox)` - If the mailbox is valid
+    /// * `Err(String)` - If the mailbox is invalid, with an error message
+    pub fn parse(s: String) -> Result<Mailbox, String> {
+        let trimmed = s.trim();
+        let Some(address) = trimmed.strip_suffix('>') else {
+            return SubscriberEmail::parse(trimmed.to_string()).map(Self::from);
+        };
+        let Some((name, address)) = address.rsplit_once('<') else {
+            return Err(format!("'{}' is not a valid mailbox", s));
+        };
+
+        let address = SubscriberEmail::parse(address.trim().to_string())?;
+        let display_name = parse_display_name(name.trim())
+            .ok_or_else(|| format!("'{}' has an invalid display name", s))?;
+        Ok(Self {
+            display_name,
+            address,
+        })
+    }
+
+    /// Returns the display name, if any
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name.as_deref()
+    }
+
+    /// Returns the email address
+    pub fn address(&self) -> &SubscriberEmail {
+        &self.address
+    }
+}
+
+impl From<SubscriberEmail> for Mailbox {
+    fn from(address: SubscriberEmail) -> Self {
+        Self {
+            display_name: None,
+            address,
+        }
+    }
+}
+
+/// Unquotes a display name, returning None if it is malformed
+///
+/// Control characters are rejected so a name can never inject header lines.
+fn parse_display_name(name: &str) -> Option<Option<String>> {
+    let name = match name.strip_prefix('"') {
+        Some(quoted) => {
+            let quoted = quoted.strip_suffix('"')?;
+            let mut unescaped = String::with_capacity(quoted.len());
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unescaped.push(chars.next()?),
+                    '"' => return None,
+                    c => unescaped.push(c),
+                }
+            }
+            unescaped
+        }
+        None if name.contains(['"', '<', '>']) => return None,
+        None => name.to_string(),
+    };
+
+    if name.chars().any(char::is_control) {
+        return None;
+    }
+    let name = name.trim();
+    Some((!name.is_empty()).then(|| name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn a_bare_address_is_accepted() {
+        let mailbox = assert_ok!(Mailbox::parse("news@acme.io".to_string()));
+        assert_eq!(mailbox.display_name(), None);
+        assert_eq!(mailbox.to_string(), "news@acme.io");
+    }
+
+    #[test]
+    fn a_quoted_display_name_is_accepted() {
+        let mailbox = assert_ok!(Mailbox::parse(r#""Acme News" <news@acme.io>"#.to_string()));
+        assert_eq!(mailbox.display_name(), Some("Acme News"));
+        assert_eq!(mailbox.address().as_ref(), "news@acme.io");
+        assert_eq!(mailbox.to_string(), r#""Acme News" <news@acme.io>"#);
+    }
+
+    #[test]
+    fn an_unquoted_display_name_is_accepted() {
+        let mailbox = assert_ok!(Mailbox::parse("Acme News <news@acme.io>".to_string()));
+        assert_eq!(mailbox.display_name(), Some("Acme News"));
+    }
+
+    #[test]
+    fn an_address_in_angle_brackets_without_name_is_accepted() {
+        let mailbox = assert_ok!(Mailbox::parse("<news@acme.io>".to_string()));
+        assert_eq!(mailbox.display_name(), None);
+    }
+
+    #[test]
+    fn quotes_in_display_names_are_escaped() {
+        let mailbox = assert_ok!(Mailbox::parse(
+            r#""The \"Acme\" News" <news@acme.io>"#.to_string()
+        ));
+        assert_eq!(mailbox.display_name(), Some(r#"The "Acme" News"#));
+        assert_eq!(mailbox.to_string(), r#""The \"Acme\" News" <news@acme.io>"#);
+    }
+
+    #[test]
+    fn an_invalid_address_is_rejected() {
+        assert_err!(Mailbox::parse("Acme News <news.acme.io>".to_string()));
+    }
+
+    #[test]
+    fn a_missing_opening_bracket_is_rejected() {
+        assert_err!(Mailbox::parse("Acme News news@acme.io>".to_string()));
+    }
+
+    #[test]
+    fn an_unterminated_quote_is_rejected() {
+        assert_err!(Mailbox::parse(r#""Acme News <news@acme.io>"#.to_string()));
+    }
+
+    #[test]
+    fn control_characters_in_display_names_are_rejected() {
+        assert_err!(Mailbox::parse(
+            "\"Acme\r\nBcc: all@acme.io\" <news@acme.io>".to_string()
+        ));
+    }
+}
@@ -1,7 +1,9 @@
+mod mailbox;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
 
+pub use mailbox::Mailbox;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
-pub use subscriber_name::SubscriberName;
+pub use subscriber_name::{SubscriberName, SubscriberNameError};
@@ -1,16 +1,34 @@
-use validator::ValidateEmail;
+use unicode_normalization::UnicodeNormalization;
+
+/// Maximum length of the local part, in octets
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+
+/// Maximum length of an address, in octets
+const MAX_ADDRESS_LENGTH: usize = 254;
+
+/// Printable ASCII characters allowed in a dot-atom besides letters and digits
+const ATEXT_SPECIALS: &str = "!#$%&'*+-/=?^_`{|}~";
 
 #[derive(Debug, Clone)]
-pub struct SubscriberEmail(String);
+pub struct SubscriberEmail {
+    /// Address mail is delivered to, with its domain in lowercase ASCII
+    address: String,
+    /// Address with its local part case-folded too, used to tell subscribers apart
+    normalized: String,
+}
 
 impl std::fmt::Display for SubscriberEmail {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+        self.address.fmt(f)
     }
 }
 
 impl SubscriberEmail {
-    /// Parses a string into a valid `SubscriberEmail`.
+    /// Parses a string into a valid, canonical `SubscriberEmail`.
+    ///
+    /// Surrounding whitespace is trimmed, the local part is put in Unicode NFC
+    /// (UTF-8 local parts are allowed, as with SMTPUTF8) and the domain is
+    /// case-folded and converted to its IDNA (punycode) form.
     ///
     /// # Arguments
     /// * `s` - The string to be parsed as an email address
@@ -19,20 +37,59 @@ impl SubscriberEmail {
     /// * `Ok(SubscriberEmail)` - If the email is valid
     /// * `Err(String)` - If the email is invalid, with an error message
     pub fn parse(s: String) -> Result<SubscriberEmail, String> {
-        if s.is_empty() {
+        let trimmed = s.trim();
+        if trimmed.is_empty() {
             return Err("Email address cannot be empty".to_string());
         }
+        let invalid = || format!("'{}' is not a valid email address", trimmed);
 
-        if !s.validate_email() {
-            return Err(format!("'{}' is not a valid email address", s));
+        let (local_part, domain) = trimmed.rsplit_once('@').ok_or_else(invalid)?;
+        let local_part: String = local_part.nfc().collect();
+        if !is_valid_local_part(&local_part) {
+            return Err(invalid());
+        }
+        let domain = idna::domain_to_ascii_strict(domain).map_err(|_| invalid())?;
+        if !domain.contains('.') {
+            return Err(invalid());
         }
 
-        Ok(Self(s))
+        let address = format!("{}@{}", local_part, domain);
+        if address.len() > MAX_ADDRESS_LENGTH {
+            return Err(invalid());
+        }
+        let normalized = format!("{}@{}", local_part.to_lowercase(), domain);
+        Ok(Self {
+            address,
+            normalized,
+        })
+    }
+
+    /// Returns the form an address is stored under for lookups
+    ///
+    /// Falls back to lowercasing the trimmed input when it is not a valid address,
+    /// e.g. one reported by the email service.
+    pub fn normalize(s: &str) -> String {
+        match Self::parse(s.to_string()) {
+            Ok(email) => email.normalized,
+            Err(_) => s.trim().to_lowercase(),
+        }
     }
 
     /// Returns the email address as a string reference
     pub fn as_str(&self) -> &str {
-        &self.0
+        &self.address
+    }
+
+    /// Returns the domain, in lowercase ASCII
+    pub fn domain(&self) -> &str {
+        self.address
+            .rsplit_once('@')
+            .map_or("", |(_, domain)| domain)
+    }
+
+    /// Returns the normalized address, identical for every spelling of the same mailbox
+    pub fn normalized(&self) -> &str {
+        &self.normalized
     }
 }
 
@@ -42,6 +99,20 @@ impl AsRef<str> for SubscriberEmail {
     }
 }
 
+/// Checks a dot-atom local part, extended with UTF-8 characters as allowed by RFC 6531
+fn is_valid_local_part(local_part: &str) -> bool {
+    let is_atext = |c: char| {
+        c.is_ascii_alphanumeric()
+            || ATEXT_SPECIALS.contains(c)
+            || (!c.is_ascii() && !c.is_control() && !c.is_whitespace())
+    };
+    !local_part.is_empty()
+        && local_part.len() <= MAX_LOCAL_PART_LENGTH
+        && local_part
+            .split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -57,13 +128,112 @@ mod tests {
         any::<u32>().prop_map(|_| SafeEmail().fake())
     }
 
+    /// Strategy to generate valid email addresses with randomly cased characters
+    fn case_variant_strategy() -> impl Strategy<Value = (String, String)> {
+        (fake_email_strategy(), any::<u64>()).prop_map(|(email, mask)| {
+            let variant = email
+                .chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    if mask >> (i % 64) & 1 == 1 {
+                        c.to_ascii_uppercase()
+                    } else {
+                        c
+                    }
+                })
+                .collect();
+            (email, variant)
+        })
+    }
+
     proptest!(
         #[test]
         fn valid_emails_are_parsed_successfully(email in fake_email_strategy()) {
             assert_ok!(SubscriberEmail::parse(email));
         }
+
+        #[test]
+        fn case_variants_share_the_same_normalized_form((email, variant) in case_variant_strategy()) {
+            let email = SubscriberEmail::parse(email).unwrap();
+            let variant = SubscriberEmail::parse(variant).unwrap();
+            assert_eq!(email.normalized(), variant.normalized());
+        }
+
+        #[test]
+        fn parsing_is_idempotent(email in fake_email_strategy(), padding in "[ \t]{0,3}") {
+            let email = SubscriberEmail::parse(format!("{}{}{}", padding, email, padding)).unwrap();
+            let reparsed = SubscriberEmail::parse(email.to_string()).unwrap();
+            assert_eq!(email.as_ref(), reparsed.as_ref());
+            assert_eq!(email.normalized(), reparsed.normalized());
+        }
     );
 
+    #[test]
+    fn surrounding_whitespace_is_trimmed() {
+        let email = SubscriberEmail::parse("  ursula@example.com\t".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "ursula@example.com");
+    }
+
+    #[test]
+    fn the_domain_is_case_folded_but_not_the_local_part() {
+        let email = SubscriberEmail::parse("Ursula@Example.COM".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "Ursula@example.com");
+        assert_eq!(email.normalized(), "ursula@example.com");
+    }
+
+    #[test]
+    fn internationalized_domains_are_converted_to_punycode() {
+        let email = SubscriberEmail::parse("ursula@Bücher.example".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "ursula@xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn utf8_local_parts_are_accepted() {
+        let email = SubscriberEmail::parse("用户@例子.广告".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "用户@xn--fsqu00a.xn--4rr70v");
+    }
+
+    #[test]
+    fn utf8_local_parts_are_put_in_nfc() {
+        let decomposed = SubscriberEmail::parse("jose\u{301}@example.com".to_string()).unwrap();
+        let composed = SubscriberEmail::parse("jos\u{e9}@example.com".to_string()).unwrap();
+        assert_eq!(decomposed.as_ref(), composed.as_ref());
+    }
+
+    #[test]
+    fn local_parts_with_misplaced_dots_are_rejected() {
+        for email in [
+            ".ursula@example.com",
+            "ursula.@example.com",
+            "urs..ula@example.com",
+        ] {
+            assert_err!(SubscriberEmail::parse(email.to_string()));
+        }
+    }
+
+    #[test]
+    fn local_parts_with_whitespace_are_rejected() {
+        assert_err!(SubscriberEmail::parse(
+            "ursula le guin@example.com".to_string()
+        ));
+    }
+
+    #[test]
+    fn a_local_part_longer_than_64_octets_is_rejected() {
+        let email = format!("{}@example.com", "a".repeat(65));
+        assert_err!(SubscriberEmail::parse(email));
+    }
+
+    #[test]
+    fn a_domain_without_a_dot_is_rejected() {
+        assert_err!(SubscriberEmail::parse("ursula@localhost".to_string()));
+    }
+
+    #[test]
+    fn invalid_addresses_are_normalized_by_lowercasing() {
+        assert_eq!(SubscriberEmail::normalize(" Not An Email "), "not an email");
+    }
+
     #[test]
     fn empty_string_is_rejected() {
         let email = "".to_string();
@@ -1,23 +1,93 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_properties::{GeneralCategory, UnicodeGeneralCategory};
 use unicode_segmentation::UnicodeSegmentation;
 
+use crate::error_code::ErrorCode;
+
+/// Maximum length of a name, in graphemes
+const MAX_LENGTH: usize = 256;
+
+/// Characters with a meaning in HTML, JSON or email headers
+const FORBIDDEN_CHARACTERS: [char; 9] = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
+
 #[derive(Debug)]
 pub struct SubscriberName(String);
 
+/// Reason a subscriber name was rejected
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum SubscriberNameError {
+    #[error("The name is empty.")]
+    Empty,
+    #[error("The name is longer than {max} characters.")]
+    TooLong { max: usize },
+    /// `position` counts characters from 1 in the name as submitted
+    #[error(
+        "The name contains a forbidden character {} at position {position}.",
+        describe(.character)
+    )]
+    ForbiddenCharacter { character: char, position: usize },
+}
+
+impl SubscriberNameError {
+    /// Returns the error code of the rejection
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Empty => ErrorCode::EmptyName,
+            Self::TooLong { .. } => ErrorCode::NameTooLong,
+            Self::ForbiddenCharacter { .. } => ErrorCode::ForbiddenNameCharacter,
+        }
+    }
+}
+
 impl SubscriberName {
     /// Parse a string into a valid `SubscriberName`.
-    pub fn parse(s: String) -> Result<SubscriberName, String> {
-        let is_empty_or_whitespace = s.trim().is_empty();
+    ///
+    /// The name is normalized to NFC, trimmed and its whitespace runs are collapsed
+    /// into single spaces before being validated.
+    pub fn parse(s: String) -> Result<SubscriberName, SubscriberNameError> {
+        let name = s
+            .nfc()
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
 
-        let is_too_long = s.graphemes(true).count() > 256;
+        if name.is_empty() {
+            return Err(SubscriberNameError::Empty);
+        }
+        if name.graphemes(true).count() > MAX_LENGTH {
+            return Err(SubscriberNameError::TooLong { max: MAX_LENGTH });
+        }
+        // Normalizing neither adds nor removes forbidden characters, only whitespace, so
+        // they are looked up where the user typed them
+        if let Some((index, character)) = s
+            .chars()
+            .enumerate()
+            .find(|(_, c)| !c.is_whitespace() && is_forbidden(*c))
+        {
+            return Err(SubscriberNameError::ForbiddenCharacter {
+                character,
+                position: index + 1,
+            });
+        }
+        Ok(SubscriberName(name))
+    }
+}
 
-        let forbidden_characters = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
-        let contains_forbidden_characters = s.chars().any(|c| forbidden_characters.contains(&c));
+/// Tells whether a character is forbidden, including the invisible format characters
+/// (Unicode category Cf) reordering or joining the text around them, e.g. bidi overrides
+fn is_forbidden(c: char) -> bool {
+    c.is_control()
+        || FORBIDDEN_CHARACTERS.contains(&c)
+        || c.general_category() == GeneralCategory::Format
+}
 
-        if is_empty_or_whitespace || is_too_long || contains_forbidden_characters {
-            Err("invalid subscriber name".to_string())
-        } else {
-            Ok(SubscriberName(s))
-        }
+/// Quotes a visible character, invisible ones are shown by their code point
+fn describe(c: &char) -> String {
+    if c.is_control() || c.general_category() == GeneralCategory::Format {
+        format!("U+{:04X}", u32::from(*c))
+    } else {
+        format!("'{}'", c)
     }
 }
 
@@ -41,19 +111,28 @@ mod tests {
     #[test]
     fn a_name_longer_than_256_graphemes_is_rejected() {
         let name = "ä".repeat(257);
-        assert_err!(SubscriberName::parse(name));
+        assert_eq!(
+            SubscriberName::parse(name).unwrap_err(),
+            SubscriberNameError::TooLong { max: 256 }
+        );
     }
 
     #[test]
     fn whitespace_only_names_are_rejected() {
-        let name = " ".to_string();
-        assert_err!(SubscriberName::parse(name));
+        let name = " \t\u{3000}".to_string();
+        assert_eq!(
+            SubscriberName::parse(name).unwrap_err(),
+            SubscriberNameError::Empty
+        );
     }
 
     #[test]
     fn empty_string_is_rejected() {
         let name = "".to_string();
-        assert_err!(SubscriberName::parse(name));
+        assert_eq!(
+            SubscriberName::parse(name).unwrap_err(),
+            SubscriberNameError::Empty
+        );
     }
 
     #[test]
@@ -64,6 +143,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn names_containing_control_or_invisible_characters_are_rejected() {
+        for c in [
+            '\u{0000}', '\u{001B}', '\u{007F}', '\u{202E}', '\u{2066}', '\u{200B}', '\u{2060}',
+            '\u{00AD}',
+        ] {
+            let name = format!("Ada{}Lovelace", c);
+            assert_eq!(
+                SubscriberName::parse(name).unwrap_err(),
+                SubscriberNameError::ForbiddenCharacter {
+                    character: c,
+                    position: 4
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn the_position_of_a_forbidden_character_is_reported() {
+        let error = SubscriberName::parse("  Ada   <Lovelace>".to_string()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The name contains a forbidden character '<' at position 9."
+        );
+    }
+
+    #[test]
+    fn invisible_characters_are_reported_by_their_code_point() {
+        let error = SubscriberName::parse("Ada\u{202E}Lovelace".to_string()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The name contains a forbidden character U+202E at position 4."
+        );
+    }
+
+    #[test]
+    fn whitespace_is_trimmed_and_collapsed() {
+        let name = assert_ok!(SubscriberName::parse(" Ada \t\n  Lovelace ".to_string()));
+        assert_eq!(name.as_ref(), "Ada Lovelace");
+    }
+
+    #[test]
+    fn names_are_normalized_to_nfc() {
+        let decomposed = "Ame\u{0301}lie".to_string();
+        let name = assert_ok!(SubscriberName::parse(decomposed));
+        assert_eq!(name.as_ref(), "Am\u{00E9}lie");
+    }
+
     #[test]
     fn a_valid_name_is_parsed_successfully() {
         let name = "Ada Lovelace".to_string();
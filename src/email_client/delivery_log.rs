@@ -0,0 +1,149 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use super::{EmailError, SendOutcome};
+use crate::domain::SubscriberEmail;
+
+/// What a message is sent for, recorded in the delivery log
+#[derive(Debug, Clone, Copy)]
+pub enum EmailKind {
+    Confirmation,
+    Newsletter { issue_id: Uuid },
+    Test,
+}
+
+impl EmailKind {
+    /// Returns the kind name as stored in the delivery log
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Confirmation => "confirmation",
+            Self::Newsletter { .. } => "newsletter",
+            Self::Test => "test",
+        }
+    }
+
+    fn issue_id(&self) -> Option<Uuid> {
+        match self {
+            Self::Newsletter { issue_id } => Some(*issue_id),
+            _ => None,
+        }
+    }
+}
+
+/// One message handed to the email service
+pub(super) struct Delivery {
+    recipient: String,
+    recipient_normalized: String,
+    status: &'static str,
+    provider_message_id: Option<String>,
+    error: Option<String>,
+}
+
+impl Delivery {
+    /// Describes the outcome of a message
+    pub(super) fn new(
+        recipient: &SubscriberEmail,
+        result: &Result<SendOutcome, EmailError>,
+    ) -> Self {
+        match result {
+            Ok(outcome) => Self {
+                recipient: recipient.to_string(),
+                recipient_normalized: recipient.normalized().to_string(),
+                status: "accepted",
+                provider_message_id: outcome.message_id.clone(),
+                error: None,
+            },
+            Err(e) => Self::failed(recipient, e),
+        }
+    }
+
+    /// Describes a message that was not accepted
+    fn failed(recipient: &SubscriberEmail, error: &EmailError) -> Self {
+        let status = match error {
+            EmailError::Transient(_) => "transient_failure",
+            EmailError::PermanentRecipient { .. } => "rejected",
+            EmailError::Configuration { .. } => "failed",
+        };
+        Self {
+            recipient: recipient.to_string(),
+            recipient_normalized: recipient.normalized().to_string(),
+            status,
+            provider_message_id: None,
+            error: Some(format!("{:?}", error)),
+        }
+    }
+}
+
+/// Auditable record of every message sent through an `EmailClient`
+#[derive(Clone)]
+pub struct DeliveryLog {
+    pool: PgPool,
+}
+
+impl DeliveryLog {
+    /// Creates a delivery log writing to the `email_log` table
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Records deliveries sharing the same request
+    ///
+    /// Failing to write the log never fails the send itself, the message is already gone.
+    pub(super) async fn record(
+        &self,
+        kind: EmailKind,
+        latency: Duration,
+        deliveries: Vec<Delivery>,
+    ) {
+        if let Err(e) = self.insert(kind, latency, deliveries).await {
+            error!(error.cause_chain = ?e, "Failed to record email deliveries");
+        }
+    }
+
+    #[instrument(name = "Record email deliveries", skip_all, fields(kind = kind.as_str()))]
+    async fn insert(
+        &self,
+        kind: EmailKind,
+        latency: Duration,
+        deliveries: Vec<Delivery>,
+    ) -> Result<(), sqlx::Error> {
+        let mut ids = Vec::with_capacity(deliveries.len());
+        let mut recipients = Vec::with_capacity(deliveries.len());
+        let mut normalized_recipients = Vec::with_capacity(deliveries.len());
+        let mut statuses = Vec::with_capacity(deliveries.len());
+        let mut message_ids = Vec::with_capacity(deliveries.len());
+        let mut errors = Vec::with_capacity(deliveries.len());
+        for delivery in deliveries {
+            ids.push(Uuid::new_v4());
+            recipients.push(delivery.recipient);
+            normalized_recipients.push(delivery.recipient_normalized);
+            statuses.push(delivery.status.to_string());
+            message_ids.push(delivery.provider_message_id);
+            errors.push(delivery.error);
+        }
+        let latency_millis = i32::try_from(latency.as_millis()).unwrap_or(i32::MAX);
+
+        sqlx::query!(
+            r#"INSERT INTO email_log
+            (id, recipient, recipient_normalized, status, provider_message_id, error, kind, issue_id, latency_millis, sent_at)
+            SELECT *, $7, $8::uuid, $9, $10
+            FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[], $5::text[], $6::text[])"#,
+            &ids,
+            &recipients,
+            &normalized_recipients,
+            &statuses,
+            &message_ids as &[Option<String>],
+            &errors as &[Option<String>],
+            kind.as_str(),
+            kind.issue_id(),
+            latency_millis,
+            Utc::now()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
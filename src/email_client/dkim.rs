@@ -0,0 +1,349 @@
+use anyhow::Context;
+use lettre::{
+    message::{
+        dkim::{
+            DkimCanonicalization, DkimCanonicalizationType, DkimConfig, DkimSigningAlgorithm,
+            DkimSigningKey,
+        },
+        header::HeaderName,
+    },
+    Message,
+};
+use secrecy::ExposeSecret;
+
+use crate::{
+    configuration::{DkimAlgorithm, DkimSettings},
+    domain::Mailbox,
+};
+
+/// Headers covered by the signature
+///
+/// `Reply-To` and `Cc` are signed even when a message has none, so that they can't be
+/// added in transit either. `Content-Type` is left out: lettre only adds it to multipart
+/// messages when they are formatted, after signing, so it would be listed in the
+/// signature without being hashed.
+const SIGNED_HEADERS: [&str; 8] = [
+    "From",
+    "To",
+    "Cc",
+    "Reply-To",
+    "Subject",
+    "Date",
+    "Message-ID",
+    "MIME-Version",
+];
+
+/// Signs outgoing MIME messages with DKIM
+pub struct DkimSigner {
+    config: DkimConfig,
+    /// Signing domain, in lowercase
+    domain: String,
+}
+
+impl DkimSigner {
+    /// Creates a signer from its settings
+    ///
+    /// # Arguments
+    /// * `conf` - DKIM settings, the private key is read from `private_key` or,
+    ///   if unset, from the file at `private_key_path`
+    ///
+    /// # Returns
+    /// A new DkimSigner if the private key could be loaded, Error otherwise
+    pub fn new(conf: &DkimSettings) -> anyhow::Result<Self> {
+        let private_key = match (&conf.private_key, &conf.private_key_path) {
+            (Some(private_key), _) => private_key.expose_secret().to_string(),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read the DKIM private key from {}", path))?,
+            (None, None) => anyhow::bail!("Either private_key or private_key_path must be set"),
+        };
+        let algorithm = match conf.algorithm {
+            DkimAlgorithm::Rsa => DkimSigningAlgorithm::Rsa,
+            DkimAlgorithm::Ed25519 => DkimSigningAlgorithm::Ed25519,
+        };
+        let private_key = DkimSigningKey::new(private_key.trim(), algorithm)
+            .context("Invalid DKIM private key")?;
+
+        let config = DkimConfig::new(
+            conf.selector.clone(),
+            conf.domain.clone(),
+            private_key,
+            SIGNED_HEADERS
+                .into_iter()
+                .map(HeaderName::new_from_ascii_str)
+                .collect(),
+            DkimCanonicalization {
+                header: DkimCanonicalizationType::Relaxed,
+                body: DkimCanonicalizationType::Relaxed,
+            },
+        );
+        Ok(Self {
+            config,
+            domain: conf.domain.to_ascii_lowercase(),
+        })
+    }
+
+    /// Returns the signing domain
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Tells whether a sender is aligned with the signing domain, as DMARC requires
+    ///
+    /// Without the public suffix list, relaxed alignment is approximated: either domain
+    /// must be the other one or one of its subdomains.
+    pub fn is_aligned(&self, sender: &Mailbox) -> bool {
+        let sender = sender.address().domain();
+        let is_within = |domain: &str, parent: &str| {
+            domain == parent
+                || domain
+                    .strip_suffix(parent)
+                    .is_some_and(|subdomain| subdomain.ends_with('.'))
+        };
+        is_within(sender, &self.domain) || is_within(&self.domain, sender)
+    }
+
+    /// Adds a `DKIM-Signature` header to a message
+    pub(super) fn sign(&self, message: &mut Message) {
+        message.sign(&self.config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{smtp::build_message, EmailMessage};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use claims::{assert_err, assert_ok};
+    use rsa::{pkcs1::DecodeRsaPrivateKey, Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+    use secrecy::SecretString;
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+
+    const RSA_KEY_PATH: &str = "tests/fixtures/dkim_rsa.pem";
+    const ED25519_SEED: [u8; 32] = [7; 32];
+
+    fn settings(algorithm: DkimAlgorithm, private_key: Option<String>) -> DkimSettings {
+        DkimSettings {
+            selector: "newsletter".to_string(),
+            domain: "acme.io".to_string(),
+            algorithm,
+            private_key: private_key.map(SecretString::from),
+            private_key_path: Some(RSA_KEY_PATH.to_string()),
+        }
+    }
+
+    /// Builds a message the way the SMTP relay does
+    fn message() -> Message {
+        let from = Mailbox::parse("\"Acme News\" <news@acme.io>".to_string()).unwrap();
+        let recipient = SubscriberEmail::parse("ursula@example.com".to_string()).unwrap();
+        let message = EmailMessage::new(
+            "Spring issue",
+            "<p>Hello  there</p>",
+            "Hello  there \r\n\r\n",
+        );
+        build_message(&from, None, &recipient, &message).unwrap()
+    }
+
+    /// Splits a message into its unfolded headers and its body
+    fn split(raw: &str) -> (Vec<(String, String)>, &str) {
+        let (head, body) = raw.split_once("\r\n\r\n").unwrap();
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in head.split("\r\n") {
+            if line.starts_with([' ', '\t']) {
+                headers.last_mut().unwrap().1.push_str(line);
+            } else {
+                let (name, value) = line.split_once(':').unwrap();
+                headers.push((name.to_string(), value.to_string()));
+            }
+        }
+        (headers, body)
+    }
+
+    /// Collapses whitespace runs and trims, as relaxed canonicalization does
+    fn collapse(s: &str) -> String {
+        s.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Relaxed body canonicalization, RFC 6376 section 3.4.4
+    fn canonical_body(body: &str) -> String {
+        let lines: Vec<String> = body
+            .split("\r\n")
+            .map(|line| {
+                let mut canonical = String::new();
+                for c in line.chars() {
+                    match c {
+                        ' ' | '\t' if canonical.ends_with(' ') => {}
+                        ' ' | '\t' => canonical.push(' '),
+                        c => canonical.push(c),
+                    }
+                }
+                canonical.trim_end().to_string()
+            })
+            .collect();
+        let end = lines
+            .iter()
+            .rposition(|l| !l.is_empty())
+            .map_or(0, |i| i + 1);
+        lines[..end].iter().map(|l| format!("{}\r\n", l)).collect()
+    }
+
+    /// Verifies the DKIM signature of a message, returning the signing domain
+    fn verify(
+        raw: &str,
+        verify_signature: impl Fn(&[u8], &[u8]) -> bool,
+    ) -> Result<String, String> {
+        let (headers, body) = split(raw);
+        let (_, signature_header) = headers
+            .iter()
+            .rev()
+            .find(|(name, _)| name.eq_ignore_ascii_case("DKIM-Signature"))
+            .ok_or("No DKIM-Signature header")?;
+        let tags: HashMap<String, String> = signature_header
+            .split(';')
+            .filter_map(|tag| tag.split_once('='))
+            .map(|(name, value)| (name.trim().to_string(), value.split_whitespace().collect()))
+            .collect();
+
+        let body_hash = STANDARD.encode(Sha256::digest(canonical_body(body)));
+        if tags["bh"] != body_hash {
+            return Err("Body hash mismatch".to_string());
+        }
+
+        let mut hasher = Sha256::new();
+        for name in tags["h"].split(':') {
+            // A signed header the message lacks hashes as nothing, RFC 6376 section 5.4
+            let Some((_, value)) = headers
+                .iter()
+                .rev()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            else {
+                continue;
+            };
+            hasher.update(format!("{}:{}\r\n", name.to_lowercase(), collapse(value)));
+        }
+        let unsigned: Vec<String> = signature_header
+            .split(';')
+            .map(|tag| match tag.split_once('=') {
+                Some((name, _)) if name.trim() == "b" => format!("{}=", name),
+                _ => tag.to_string(),
+            })
+            .collect();
+        hasher.update(format!("dkim-signature:{}", collapse(&unsigned.join(";"))));
+
+        let signature = STANDARD.decode(&tags["b"]).map_err(|e| e.to_string())?;
+        if verify_signature(&hasher.finalize(), &signature) {
+            Ok(tags["d"].clone())
+        } else {
+            Err("Signature mismatch".to_string())
+        }
+    }
+
+    fn verify_rsa(raw: &str) -> Result<String, String> {
+        let pem = std::fs::read_to_string(RSA_KEY_PATH).unwrap();
+        let public_key = RsaPublicKey::from(RsaPrivateKey::from_pkcs1_pem(&pem).unwrap());
+        verify(raw, |hash, signature| {
+            public_key
+                .verify(Pkcs1v15Sign::new::<Sha256>(), hash, signature)
+                .is_ok()
+        })
+    }
+
+    fn verify_ed25519(raw: &str) -> Result<String, String> {
+        let public_key = ed25519_dalek::SigningKey::from_bytes(&ED25519_SEED).verifying_key();
+        verify(raw, |hash, signature| {
+            ed25519_dalek::Signature::from_slice(signature)
+                .and_then(|signature| public_key.verify_strict(hash, &signature))
+                .is_ok()
+        })
+    }
+
+    #[test]
+    fn rsa_signatures_verify_against_the_public_key() {
+        let signer = assert_ok!(DkimSigner::new(&settings(DkimAlgorithm::Rsa, None)));
+        let mut message = message();
+
+        signer.sign(&mut message);
+
+        let raw = String::from_utf8(message.formatted()).unwrap();
+        assert_eq!(assert_ok!(verify_rsa(&raw)), "acme.io");
+    }
+
+    #[test]
+    fn ed25519_signatures_verify_against_the_public_key() {
+        let private_key = STANDARD.encode(ED25519_SEED);
+        let signer = assert_ok!(DkimSigner::new(&settings(
+            DkimAlgorithm::Ed25519,
+            Some(private_key)
+        )));
+        let mut message = message();
+
+        signer.sign(&mut message);
+
+        let raw = String::from_utf8(message.formatted()).unwrap();
+        assert_eq!(assert_ok!(verify_ed25519(&raw)), "acme.io");
+    }
+
+    #[test]
+    fn a_tampered_body_fails_verification() {
+        let signer = assert_ok!(DkimSigner::new(&settings(DkimAlgorithm::Rsa, None)));
+        let mut message = message();
+        signer.sign(&mut message);
+
+        let raw = String::from_utf8(message.formatted()).unwrap();
+        let tampered = raw.replace("Hello", "Howdy");
+
+        assert_err!(verify_rsa(&tampered));
+    }
+
+    #[test]
+    fn a_tampered_header_fails_verification() {
+        let signer = assert_ok!(DkimSigner::new(&settings(DkimAlgorithm::Rsa, None)));
+        let mut message = message();
+        signer.sign(&mut message);
+
+        let raw = String::from_utf8(message.formatted()).unwrap();
+        let tampered = raw.replace("Spring issue", "Winter issue");
+
+        assert_err!(verify_rsa(&tampered));
+    }
+
+    #[test]
+    fn a_reply_to_added_after_signing_fails_verification() {
+        let signer = assert_ok!(DkimSigner::new(&settings(DkimAlgorithm::Rsa, None)));
+        let mut message = message();
+        signer.sign(&mut message);
+
+        let raw = String::from_utf8(message.formatted()).unwrap();
+        let tampered = format!("Reply-To: mallory@example.com\r\n{}", raw);
+
+        assert_ok!(verify_rsa(&raw));
+        assert_err!(verify_rsa(&tampered));
+    }
+
+    #[test]
+    fn senders_on_the_signing_domain_or_its_subdomains_are_aligned() {
+        let signer = assert_ok!(DkimSigner::new(&settings(DkimAlgorithm::Rsa, None)));
+        let aligned =
+            |address: &str| signer.is_aligned(&Mailbox::parse(address.to_string()).unwrap());
+
+        assert!(aligned("news@acme.io"));
+        assert!(aligned("news@ACME.io"));
+        assert!(aligned("releases@news.acme.io"));
+        assert!(!aligned("news@other.org"));
+        assert!(!aligned("news@notacme.io"));
+    }
+
+    #[test]
+    fn a_missing_private_key_is_rejected() {
+        let mut settings = settings(DkimAlgorithm::Rsa, None);
+        settings.private_key_path = None;
+        assert!(DkimSigner::new(&settings).is_err());
+    }
+
+    #[test]
+    fn an_invalid_private_key_is_rejected() {
+        let settings = settings(DkimAlgorithm::Rsa, Some("not a key".to_string()));
+        assert!(DkimSigner::new(&settings).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use base64::Engine;
+use std::collections::BTreeMap;
+
+use crate::domain::{Mailbox, SubscriberEmail};
+
+/// Content of an email, shared by every recipient it is sent to
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub(super) from: Option<Mailbox>,
+    pub(super) subject: String,
+    pub(super) html_body: String,
+    pub(super) text_body: String,
+    pub(super) reply_to: Option<Mailbox>,
+    pub(super) cc: Vec<SubscriberEmail>,
+    pub(super) bcc: Vec<SubscriberEmail>,
+    pub(super) headers: Vec<Header>,
+    pub(super) tag: Option<String>,
+    pub(super) metadata: BTreeMap<String, String>,
+    pub(super) attachments: Vec<Attachment>,
+}
+
+impl EmailMessage {
+    /// Creates a message with a subject and HTML and plain text bodies
+    pub fn new(subject: &str, html_body: &str, text_body: &str) -> Self {
+        Self {
+            from: None,
+            subject: subject.to_string(),
+            html_body: html_body.to_string(),
+            text_body: text_body.to_string(),
+            reply_to: None,
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            headers: Vec::new(),
+            tag: None,
+            metadata: BTreeMap::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Overrides the sender configured on the client
+    pub fn from(mut self, sender: Mailbox) -> Self {
+        self.from = Some(sender);
+        self
+    }
+
+    /// Returns the sender overriding the one configured on the client, if any
+    pub fn sender(&self) -> Option<&Mailbox> {
+        self.from.as_ref()
+    }
+
+    /// Sets the mailbox replies are sent to, overriding the client default
+    pub fn reply_to(mut self, mailbox: Mailbox) -> Self {
+        self.reply_to = Some(mailbox);
+        self
+    }
+
+    /// Adds a carbon copy recipient
+    pub fn cc(mut self, address: SubscriberEmail) -> Self {
+        self.cc.push(address);
+        self
+    }
+
+    /// Adds a blind carbon copy recipient
+    pub fn bcc(mut self, address: SubscriberEmail) -> Self {
+        self.bcc.push(address);
+        self
+    }
+
+    /// Adds a custom header
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push(Header {
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+        self
+    }
+
+    /// Sets the tag used by the email service to categorize the message
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Adds a metadata entry, returned by the email service in its webhooks
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds an attachment or an inline image
+    pub fn attachment(mut self, attachment: Attachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+}
+
+/// A custom email header
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct Header {
+    pub(super) name: String,
+    pub(super) value: String,
+}
+
+/// A file attached to an email
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Attachment {
+    pub(super) name: String,
+    #[serde(serialize_with = "serialize_base64")]
+    pub(super) content: Vec<u8>,
+    pub(super) content_type: String,
+    #[serde(
+        rename = "ContentID",
+        serialize_with = "serialize_content_id",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(super) content_id: Option<String>,
+}
+
+impl Attachment {
+    /// Creates a regular file attachment
+    ///
+    /// # Arguments
+    /// * `name` - File name shown to the recipient
+    /// * `content` - Raw file content
+    /// * `content_type` - MIME type of the file, e.g. `application/pdf`
+    pub fn new(name: &str, content: &[u8], content_type: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            content: content.to_vec(),
+            content_type: content_type.to_string(),
+            content_id: None,
+        }
+    }
+
+    /// Creates an inline image, referenced from the HTML body as `cid:<content_id>`
+    pub fn inline(name: &str, content: &[u8], content_type: &str, content_id: &str) -> Self {
+        Self {
+            content_id: Some(content_id.to_string()),
+            ..Self::new(name, content, content_type)
+        }
+    }
+}
+
+/// Encodes attachment content as base64, as expected by Postmark
+fn serialize_base64<S: serde::Serializer>(
+    content: &[u8],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(content))
+}
+
+/// Formats a content id as the `cid:` reference expected by Postmark
+fn serialize_content_id<S: serde::Serializer>(
+    content_id: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match content_id {
+        Some(id) => serializer.serialize_str(&format!("cid:{}", id)),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,1529 @@
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::domain::{Mailbox, SubscriberEmail};
+use reqwest::{header, Client, StatusCode, Url};
+use secrecy::{ExposeSecret, SecretString};
+use tracing::{error, warn};
+
+mod delivery_log;
+mod dkim;
+mod message;
+mod outcome;
+mod smtp;
+mod throttle;
+
+use delivery_log::Delivery;
+pub use delivery_log::{DeliveryLog, EmailKind};
+pub use dkim::DkimSigner;
+use message::Header;
+pub use message::{Attachment, EmailMessage};
+use outcome::ProviderResponse;
+pub use outcome::{EmailError, SendOutcome};
+pub use smtp::SmtpRelay;
+pub use throttle::SendThrottle;
+
+/// Maximum number of messages accepted by a single batch request
+pub const MAX_BATCH_SIZE: usize = 500;
+
+/// Number of times a request rejected with `429 Too Many Requests` is retried
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Longest time a single message waits on the provider rate limit, its sender is
+/// usually holding an HTTP request open, e.g. a sign-up waiting on its confirmation email
+const MAX_SINGLE_SEND_WAIT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+pub struct EmailClient {
+    http_client: Client,
+    base_url: Url,
+    sender: Mailbox,
+    reply_to: Option<Mailbox>,
+    authorization_token: SecretString,
+    throttle: Arc<SendThrottle>,
+    delivery_log: Option<DeliveryLog>,
+    smtp_relay: Option<Arc<SmtpRelay>>,
+    dkim_signer: Option<Arc<DkimSigner>>,
+}
+
+impl EmailClient {
+    /// Creates a new email client
+    ///
+    /// # Arguments
+    /// * `base_url` - Base URL for the email service API
+    /// * `sender` - Mailbox messages are sent from, unless a message overrides it
+    /// * `authorization_token` - Authorization token for the email service
+    /// * `timeout` - Timeout duration for API requests
+    ///
+    /// # Returns
+    /// A new EmailClient instance
+    ///
+    /// # Panics
+    /// Panics if the base URL is invalid or if HTTP client creation fails
+    pub fn new(
+        base_url: &str,
+        sender: Mailbox,
+        authorization_token: SecretString,
+        timeout: Duration,
+    ) -> Self {
+        let base_url = Url::parse(base_url).expect("Invalid base URL provided to EmailClient");
+        let http_client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("Failed to build HTTP client");
+        Self {
+            http_client,
+            base_url,
+            sender,
+            reply_to: None,
+            authorization_token,
+            throttle: Arc::new(SendThrottle::unlimited()),
+            delivery_log: None,
+            smtp_relay: None,
+            dkim_signer: None,
+        }
+    }
+
+    /// Sets the mailbox replies are sent to, unless a message overrides it
+    pub fn with_reply_to(mut self, reply_to: Mailbox) -> Self {
+        self.reply_to = Some(reply_to);
+        self
+    }
+
+    /// Limits the pace of requests sent to the email service
+    ///
+    /// The throttle is shared by all clones of this client.
+    pub fn with_throttle(mut self, throttle: SendThrottle) -> Self {
+        self.throttle = Arc::new(throttle);
+        self
+    }
+
+    /// Records every message sent by this client in the delivery log
+    pub fn with_delivery_log(mut self, delivery_log: DeliveryLog) -> Self {
+        self.delivery_log = Some(delivery_log);
+        self
+    }
+
+    /// Delivers messages through an SMTP relay instead of the Postmark API
+    pub fn with_smtp_relay(mut self, smtp_relay: SmtpRelay) -> Self {
+        self.smtp_relay = Some(Arc::new(smtp_relay));
+        self
+    }
+
+    /// Signs every MIME message built by this client with DKIM
+    pub fn with_dkim_signer(mut self, dkim_signer: DkimSigner) -> Self {
+        self.dkim_signer = Some(Arc::new(dkim_signer));
+        self
+    }
+
+    /// Checks that messages from a sender pass DMARC once signed
+    ///
+    /// # Returns
+    /// Ok(()) unless messages are signed with DKIM for a domain the sender is not
+    /// aligned with, an error message otherwise
+    pub fn check_sender(&self, sender: &Mailbox) -> Result<(), String> {
+        match &self.dkim_signer {
+            Some(signer) if !signer.is_aligned(sender) => Err(format!(
+                "The sender {} is not on the DKIM signing domain {}.",
+                sender.address(),
+                signer.domain()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends an email to a recipient
+    ///
+    /// # Arguments
+    /// * `kind` - What the email is sent for
+    /// * `recipient` - Email address of the recipient
+    /// * `subject` - Email subject
+    /// * `html_content` - HTML formatted content of the email
+    /// * `text_content` - Plain text content of the email
+    ///
+    /// # Returns
+    /// The outcome carrying the provider message id if the email was accepted,
+    /// EmailError otherwise
+    pub async fn send_email(
+        &self,
+        kind: EmailKind,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<SendOutcome, EmailError> {
+        let message = EmailMessage::new(subject, html_content, text_content);
+        self.send_message(kind, recipient, &message).await
+    }
+
+    /// Sends a message, with its attachments and extra headers, to a recipient
+    ///
+    /// # Arguments
+    /// * `kind` - What the email is sent for
+    /// * `recipient` - Email address of the recipient
+    /// * `message` - Content of the email
+    ///
+    /// # Returns
+    /// The outcome carrying the provider message id if the email was accepted,
+    /// EmailError otherwise
+    pub async fn send_message(
+        &self,
+        kind: EmailKind,
+        recipient: &SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<SendOutcome, EmailError> {
+        let started = Instant::now();
+        let result = match &self.smtp_relay {
+            Some(relay) => self.relay(relay, recipient, message).await,
+            None => self.post_message(recipient, message).await,
+        };
+
+        self.record(
+            kind,
+            started.elapsed(),
+            vec![Delivery::new(recipient, &result)],
+        )
+        .await;
+        result
+    }
+
+    /// Sends the same email to many recipients using the batch API
+    ///
+    /// Recipients are split into chunks of at most `MAX_BATCH_SIZE` messages,
+    /// each chunk costing a single HTTP round-trip.
+    ///
+    /// # Arguments
+    /// * `kind` - What the email is sent for
+    /// * `recipients` - Email addresses of the recipients
+    /// * `subject` - Email subject
+    /// * `html_content` - HTML formatted content of the email
+    /// * `text_content` - Plain text content of the email
+    ///
+    /// # Returns
+    /// The outcome of every recipient, in the order they were given
+    pub async fn send_batch<'a>(
+        &self,
+        kind: EmailKind,
+        recipients: &'a [SubscriberEmail],
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Vec<BatchOutcome<'a>> {
+        let message = EmailMessage::new(subject, html_content, text_content);
+        self.send_message_batch(kind, recipients, &message).await
+    }
+
+    /// Sends the same message to many recipients using the batch API
+    ///
+    /// A failed batch request doesn't stop the remaining ones, its recipients are
+    /// reported with its error. Every message counts against the messages per second
+    /// limit, so the call lasts as long as the limit needs to let all of them through.
+    ///
+    /// # Arguments
+    /// * `kind` - What the email is sent for
+    /// * `recipients` - Email addresses of the recipients
+    /// * `message` - Content of the email
+    ///
+    /// # Returns
+    /// The outcome of every recipient, in the order they were given
+    pub async fn send_message_batch<'a>(
+        &self,
+        kind: EmailKind,
+        recipients: &'a [SubscriberEmail],
+        message: &EmailMessage,
+    ) -> Vec<BatchOutcome<'a>> {
+        if let Some(relay) = &self.smtp_relay {
+            // SMTP has no batch command, every message is a transaction of its own
+            let mut outcomes = Vec::with_capacity(recipients.len());
+            for recipient in recipients {
+                let started = Instant::now();
+                let result = self.relay(relay, recipient, message).await;
+                self.record(
+                    kind,
+                    started.elapsed(),
+                    vec![Delivery::new(recipient, &result)],
+                )
+                .await;
+                outcomes.push(BatchOutcome { recipient, result });
+            }
+            return outcomes;
+        }
+
+        let url = self
+            .base_url
+            .join("/email/batch")
+            .expect("Invalid email client base URL");
+
+        let mut outcomes = Vec::with_capacity(recipients.len());
+        for chunk in recipients.chunks(MAX_BATCH_SIZE) {
+            let request_body: Vec<_> = chunk
+                .iter()
+                .map(|recipient| self.request(recipient, message))
+                .collect();
+
+            let started = Instant::now();
+            let responses: Result<Vec<ProviderResponse>, EmailError> = async {
+                // Unlike a single message, a batch waits out the rate limit
+                self.post(
+                    url.clone(),
+                    &request_body,
+                    chunk.len() as u32,
+                    Duration::MAX,
+                )
+                .await?
+                .json()
+                .await
+                .map_err(EmailError::transient)
+            }
+            .await;
+            let latency = started.elapsed();
+
+            let responses = match responses {
+                Ok(responses) => responses,
+                Err(e) => {
+                    // Earlier chunks were delivered already, keep their outcomes and go on
+                    if e.is_transient() {
+                        warn!(error.cause_chain = ?e, "A batch request to the email service failed");
+                    } else {
+                        error!(error.cause_chain = ?e, "The email service rejected a batch request");
+                    }
+                    let chunk_outcomes: Vec<_> = chunk
+                        .iter()
+                        .map(|recipient| BatchOutcome {
+                            recipient,
+                            result: Err(e.for_batch_recipient()),
+                        })
+                        .collect();
+                    let deliveries = chunk_outcomes
+                        .iter()
+                        .map(|o| Delivery::new(o.recipient, &o.result))
+                        .collect();
+                    self.record(kind, latency, deliveries).await;
+                    outcomes.extend(chunk_outcomes);
+                    continue;
+                }
+            };
+
+            // Results are listed in the same order as the messages of the batch
+            let mut responses = responses.into_iter();
+            let chunk_outcomes: Vec<_> = chunk
+                .iter()
+                .map(|recipient| {
+                    let result = match responses.next() {
+                        Some(response) => response.into_result(),
+                        // Nothing tells whether the message was sent, it may be sent again
+                        None => Err(EmailError::Transient(
+                            "The email service returned no result for the message".into(),
+                        )),
+                    };
+                    BatchOutcome { recipient, result }
+                })
+                .collect();
+
+            let deliveries = chunk_outcomes
+                .iter()
+                .map(|o| Delivery::new(o.recipient, &o.result))
+                .collect();
+            self.record(kind, latency, deliveries).await;
+            outcomes.extend(chunk_outcomes);
+        }
+        outcomes
+    }
+
+    /// Sends a message to a recipient through the Postmark API
+    async fn post_message(
+        &self,
+        recipient: &SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<SendOutcome, EmailError> {
+        let url = self
+            .base_url
+            .join("/email")
+            .expect("Invalid email client base URL");
+        let response = self
+            .post(
+                url,
+                &self.request(recipient, message),
+                1,
+                MAX_SINGLE_SEND_WAIT,
+            )
+            .await?;
+        // The message was accepted, a body we can't parse only costs us its id
+        let message_id = response
+            .json::<ProviderResponse>()
+            .await
+            .ok()
+            .and_then(|r| r.message_id);
+        Ok(SendOutcome { message_id })
+    }
+
+    /// Sends a message to a recipient through the SMTP relay
+    async fn relay(
+        &self,
+        relay: &SmtpRelay,
+        recipient: &SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<SendOutcome, EmailError> {
+        let from = message.from.as_ref().unwrap_or(&self.sender);
+        let reply_to = message.reply_to.as_ref().or(self.reply_to.as_ref());
+        let mut mime = smtp::build_message(from, reply_to, recipient, message)?;
+        if let Some(dkim_signer) = &self.dkim_signer {
+            dkim_signer.sign(&mut mime);
+        }
+        let _permit = self.throttle.acquire(1).await;
+        relay.send(mime).await
+    }
+
+    /// Builds the request sending a message to a recipient
+    fn request<'a>(
+        &self,
+        recipient: &'a SubscriberEmail,
+        message: &'a EmailMessage,
+    ) -> SendEmailRequest<'a> {
+        let from = message.from.as_ref().unwrap_or(&self.sender);
+        let reply_to = message.reply_to.as_ref().or(self.reply_to.as_ref());
+        SendEmailRequest {
+            from: from.to_string(),
+            to: recipient.as_ref(),
+            cc: join_addresses(&message.cc),
+            bcc: join_addresses(&message.bcc),
+            reply_to: reply_to.map(ToString::to_string),
+            subject: &message.subject,
+            html_body: &message.html_body,
+            text_body: &message.text_body,
+            tag: message.tag.as_deref(),
+            headers: &message.headers,
+            metadata: &message.metadata,
+            attachments: &message.attachments,
+        }
+    }
+
+    /// Writes deliveries to the delivery log, if one is configured
+    async fn record(&self, kind: EmailKind, latency: Duration, deliveries: Vec<Delivery>) {
+        if let Some(delivery_log) = &self.delivery_log {
+            delivery_log.record(kind, latency, deliveries).await;
+        }
+    }
+
+    /// Posts a JSON body to the email service, retrying when rate limited
+    ///
+    /// # Arguments
+    /// * `url` - Endpoint of the email service
+    /// * `body` - Request body, serialized as JSON
+    /// * `messages` - Number of messages the body carries
+    /// * `max_wait` - Longest time spent waiting on the rate limit before failing
+    ///   with a transient error
+    async fn post<T: serde::Serialize + ?Sized>(
+        &self,
+        url: Url,
+        body: &T,
+        messages: u32,
+        max_wait: Duration,
+    ) -> Result<reqwest::Response, EmailError> {
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            if self.throttle.pause_remaining() > max_wait.saturating_sub(started.elapsed()) {
+                return Err(EmailError::Transient(
+                    "The email service is rate limiting us".into(),
+                ));
+            }
+            let permit = self.throttle.acquire(messages).await;
+            let response = self
+                .http_client
+                .post(url.clone())
+                .header(
+                    "X-Postmark-Server-Token",
+                    self.authorization_token.expose_secret(),
+                )
+                .json(body)
+                .send()
+                .await
+                .map_err(EmailError::transient)?;
+            drop(permit);
+
+            if response.status() == StatusCode::TOO_MANY_REQUESTS
+                && attempt < MAX_RATE_LIMIT_RETRIES
+            {
+                // Back off every sender sharing this client, not just this request
+                let retry_after = retry_after(&response);
+                warn!(?retry_after, "The email service is rate limiting us");
+                self.throttle.pause_for(retry_after);
+                attempt += 1;
+                continue;
+            }
+
+            let status = response.status();
+            if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+                // Postmark explains every rejection with an error code
+                return Err(match response.json::<ProviderResponse>().await {
+                    Ok(body) => EmailError::from_provider(body.error_code, body.message),
+                    Err(_) => EmailError::Configuration {
+                        error_code: None,
+                        message: format!("{}, with a response that could not be parsed", status),
+                    },
+                });
+            }
+            return response.error_for_status().map_err(EmailError::transient);
+        }
+    }
+}
+
+/// Delivery outcome of one recipient of a batch
+#[derive(Debug)]
+pub struct BatchOutcome<'a> {
+    pub recipient: &'a SubscriberEmail,
+    pub result: Result<SendOutcome, EmailError>,
+}
+
+/// Reads the delay requested by a `Retry-After` header, defaulting to one second
+fn retry_after(response: &reqwest::Response) -> Duration {
+    response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(1))
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: String,
+    to: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bcc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<String>,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    headers: &'a [Header],
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: &'a BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    attachments: &'a [Attachment],
+}
+
+/// Formats addresses as the comma separated list expected by Postmark
+fn join_addresses(addresses: &[SubscriberEmail]) -> Option<String> {
+    if addresses.is_empty() {
+        return None;
+    }
+    let addresses: Vec<&str> = addresses.iter().map(AsRef::as_ref).collect();
+    Some(addresses.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{
+        Attachment, EmailClient, EmailError, EmailKind, EmailMessage, SendThrottle, MAX_BATCH_SIZE,
+        MAX_RATE_LIMIT_RETRIES, MAX_SINGLE_SEND_WAIT,
+    };
+    use claims::{assert_err, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use secrecy::SecretString;
+    use std::num::{NonZeroU32, NonZeroUsize};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tracing::Level;
+    use tracing_subscriber::layer::{self, Layer, SubscriberExt};
+    use wiremock::matchers::{any, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            // Try to parse request body as JSON
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+
+            if let Ok(body) = result {
+                // Verify all required fields are present
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlBody").is_some()
+                    && body.get("TextBody").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    /// Answers a batch request with a successful result for every message
+    struct AcceptEveryMessage;
+
+    impl wiremock::Respond for AcceptEveryMessage {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let messages: Vec<serde_json::Value> = serde_json::from_slice(&request.body).unwrap();
+            let results: Vec<_> = messages
+                .iter()
+                .map(|m| {
+                    serde_json::json!({
+                        "ErrorCode": 0,
+                        "Message": "OK",
+                        "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+                        "To": m["To"],
+                    })
+                })
+                .collect();
+            ResponseTemplate::new(200).set_body_json(results)
+        }
+    }
+
+    /// Records the level of every event logged while it is the default subscriber
+    struct LevelRecorder(Arc<Mutex<Vec<Level>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for LevelRecorder {
+        fn on_event(&self, event: &tracing::Event<'_>, _: layer::Context<'_, S>) {
+            self.0.lock().unwrap().push(*event.metadata().level());
+        }
+    }
+
+    /// Returns a random email subject
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    /// Returns a random email content
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    /// Returns a random valid email address
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    /// Creates an EmailClient instance for testing
+    fn email_client(base_url: &str) -> EmailClient {
+        let authorization_token = SecretString::from(Faker.fake::<String>());
+        let timeout = std::time::Duration::from_millis(200);
+        EmailClient::new(base_url, email().into(), authorization_token, timeout)
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_the_expected_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // execute
+        let _ = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // assert
+    }
+
+    #[tokio::test]
+    async fn send_email_succeeds_if_the_server_returns_200() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_returns_500() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        let response = ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(90));
+
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_retries_after_the_server_returns_429() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_keeps_returning_429() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .expect(u64::from(MAX_RATE_LIMIT_RETRIES) + 1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_fast_if_the_server_asks_to_wait_too_long() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "30"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let start = Instant::now();
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(error.is_transient());
+        assert!(start.elapsed() < MAX_SINGLE_SEND_WAIT);
+    }
+
+    #[tokio::test]
+    async fn send_email_honors_the_messages_per_second_limit() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri()).with_throttle(SendThrottle::new(
+            NonZeroU32::new(2).unwrap(),
+            NonZeroUsize::new(10).unwrap(),
+        ));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(4)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let start = Instant::now();
+        let sends = (0..4).map(|_| {
+            let email_client = email_client.clone();
+            tokio::spawn(async move {
+                email_client
+                    .send_email(
+                        EmailKind::Test,
+                        &email(),
+                        &subject(),
+                        &content(),
+                        &content(),
+                    )
+                    .await
+            })
+        });
+        for send in sends.collect::<Vec<_>>() {
+            assert_ok!(send.await.unwrap());
+        }
+
+        // Assert
+        // a burst of two, then one message every 500ms
+        assert!(start.elapsed() >= Duration::from_millis(900));
+    }
+
+    #[tokio::test]
+    async fn send_batch_counts_every_message_against_the_messages_per_second_limit() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri()).with_throttle(SendThrottle::new(
+            NonZeroU32::new(2).unwrap(),
+            NonZeroUsize::new(10).unwrap(),
+        ));
+        let recipients: Vec<_> = (0..5).map(|_| email()).collect();
+
+        Mock::given(any())
+            .respond_with(AcceptEveryMessage)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let start = Instant::now();
+        let outcomes = email_client
+            .send_batch(
+                EmailKind::Test,
+                &recipients,
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert!(outcomes.iter().all(|o| o.result.is_ok()));
+        // a burst of two, then one message every 500ms
+        assert!(start.elapsed() >= Duration::from_millis(1400));
+    }
+
+    #[tokio::test]
+    async fn send_email_honors_the_max_concurrency() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri()).with_throttle(SendThrottle::new(
+            NonZeroU32::new(100).unwrap(),
+            NonZeroUsize::new(1).unwrap(),
+        ));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(50)))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let start = Instant::now();
+        let sends = (0..3).map(|_| {
+            let email_client = email_client.clone();
+            tokio::spawn(async move {
+                email_client
+                    .send_email(
+                        EmailKind::Test,
+                        &email(),
+                        &subject(),
+                        &content(),
+                        &content(),
+                    )
+                    .await
+            })
+        });
+        for send in sends.collect::<Vec<_>>() {
+            assert_ok!(send.await.unwrap());
+        }
+
+        // Assert
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn send_batch_sends_the_expected_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+        let recipients = vec![email(), email()];
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email/batch"))
+            .and(method("POST"))
+            .and(|request: &Request| {
+                let body: Vec<serde_json::Value> = serde_json::from_slice(&request.body).unwrap();
+                body.len() == 2 && body.iter().all(|m| m.get("To").is_some())
+            })
+            .respond_with(AcceptEveryMessage)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(
+                EmailKind::Test,
+                &recipients,
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|o| o.result.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn send_batch_splits_recipients_into_chunks() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+        let recipients: Vec<_> = (0..MAX_BATCH_SIZE + 1).map(|_| email()).collect();
+
+        Mock::given(path("/email/batch"))
+            .respond_with(AcceptEveryMessage)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(
+                EmailKind::Test,
+                &recipients,
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_eq!(outcomes.len(), MAX_BATCH_SIZE + 1);
+    }
+
+    #[tokio::test]
+    async fn send_batch_maps_results_back_to_recipients() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+        let recipients = vec![email(), email()];
+
+        let results = serde_json::json!([
+            {"ErrorCode": 0, "Message": "OK", "To": recipients[0].as_ref()},
+            {"ErrorCode": 406, "Message": "Inactive recipient", "To": recipients[1].as_ref()},
+        ]);
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(results))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(
+                EmailKind::Test,
+                &recipients,
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_eq!(outcomes[0].recipient.as_ref(), recipients[0].as_ref());
+        assert_ok!(&outcomes[0].result);
+        assert_eq!(outcomes[1].recipient.as_ref(), recipients[1].as_ref());
+        assert!(matches!(
+            outcomes[1].result,
+            Err(EmailError::PermanentRecipient { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_batch_reports_missing_results_as_transient_failures() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+        let recipients = vec![email(), email()];
+
+        let results = serde_json::json!([
+            {"ErrorCode": 0, "Message": "OK", "To": recipients[0].as_ref()},
+        ]);
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(results))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(
+                EmailKind::Test,
+                &recipients,
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_ok!(&outcomes[0].result);
+        assert!(assert_err!(&outcomes[1].result).is_transient());
+    }
+
+    #[tokio::test]
+    async fn send_batch_reports_a_failed_chunk_as_transient_failures() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+        let recipients = vec![email()];
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(
+                EmailKind::Test,
+                &recipients,
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_eq!(outcomes.len(), 1);
+        assert!(assert_err!(&outcomes[0].result).is_transient());
+    }
+
+    #[tokio::test]
+    async fn send_batch_reports_a_rejected_chunk_as_configuration_errors() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+        let recipients = vec![email(), email()];
+        let levels = Arc::new(Mutex::new(Vec::new()));
+        let _subscriber = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(LevelRecorder(levels.clone())),
+        );
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+                "ErrorCode": 10,
+                "Message": "The Server Token you provided in the X-Postmark-Server-Token request header was invalid."
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(
+                EmailKind::Test,
+                &recipients,
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        for outcome in &outcomes {
+            let error = assert_err!(&outcome.result);
+            assert!(matches!(
+                error,
+                EmailError::Configuration {
+                    error_code: Some(10),
+                    ..
+                }
+            ));
+        }
+        assert!(levels.lock().unwrap().contains(&Level::ERROR));
+    }
+
+    #[tokio::test]
+    async fn send_batch_keeps_going_after_a_failed_chunk() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+        let recipients: Vec<_> = (0..2 * MAX_BATCH_SIZE + 1).map(|_| email()).collect();
+
+        Mock::given(path("/email/batch"))
+            .respond_with(AcceptEveryMessage)
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/email/batch"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/email/batch"))
+            .respond_with(AcceptEveryMessage)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_batch(
+                EmailKind::Test,
+                &recipients,
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_eq!(outcomes.len(), recipients.len());
+        let (first, rest) = outcomes.split_at(MAX_BATCH_SIZE);
+        let (failed, last) = rest.split_at(MAX_BATCH_SIZE);
+        assert!(first.iter().all(|o| o.result.is_ok()));
+        assert!(failed
+            .iter()
+            .all(|o| o.result.as_ref().is_err_and(EmailError::is_transient)));
+        assert!(last.iter().all(|o| o.result.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn send_email_returns_the_provider_message_id() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        let body = serde_json::json!({
+            "ErrorCode": 0,
+            "Message": "OK",
+            "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+        });
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(
+            outcome.message_id.as_deref(),
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_inactive_recipients_as_permanent_errors() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        let body = serde_json::json!({
+            "ErrorCode": 406,
+            "Message": "You tried to send to a recipient that has been marked as inactive.",
+        });
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422).set_body_json(body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert!(matches!(
+            outcome,
+            Err(EmailError::PermanentRecipient {
+                error_code: 406,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_invalid_tokens_as_configuration_errors() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        let body = serde_json::json!({
+            "ErrorCode": 10,
+            "Message": "The Server Token you provided in the X-Postmark-Server-Token request header was invalid.",
+        });
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(401).set_body_json(body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert!(matches!(
+            outcome,
+            Err(EmailError::Configuration {
+                error_code: Some(10),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_unparsable_rejections_without_an_error_code() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(400).set_body_string("<html>Bad Request</html>"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(matches!(
+            error,
+            EmailError::Configuration {
+                error_code: None,
+                ..
+            }
+        ));
+        assert!(!error.to_string().contains("code"));
+    }
+
+    #[tokio::test]
+    async fn send_email_reports_server_errors_as_transient() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert!(outcome.unwrap_err().is_transient());
+    }
+
+    /// Returns the JSON bodies of the requests received by the mock server
+    async fn received_bodies(mock_server: &MockServer) -> Vec<serde_json::Value> {
+        mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| serde_json::from_slice(&r.body).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn send_email_omits_optional_fields() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                EmailKind::Test,
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+            )
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        let body = &received_bodies(&mock_server).await[0];
+        for field in [
+            "Cc",
+            "Bcc",
+            "ReplyTo",
+            "Tag",
+            "Headers",
+            "Metadata",
+            "Attachments",
+        ] {
+            assert!(body.get(field).is_none(), "{} should be omitted", field);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_message_sends_every_extra_field() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+        let (reply_to, cc_1, cc_2, bcc) = (email(), email(), email(), email());
+        let message = EmailMessage::new(&subject(), &content(), &content())
+            .reply_to(reply_to.clone().into())
+            .cc(cc_1.clone())
+            .cc(cc_2.clone())
+            .bcc(bcc.clone())
+            .header("X-Campaign", "spring")
+            .tag("welcome")
+            .metadata("subscriber_id", "42");
+
+        Mock::given(path("/email"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_message(EmailKind::Test, &email(), &message)
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        let body = &received_bodies(&mock_server).await[0];
+        assert_eq!(body["ReplyTo"], reply_to.as_ref());
+        assert_eq!(body["Cc"], format!("{},{}", cc_1.as_ref(), cc_2.as_ref()));
+        assert_eq!(body["Bcc"], bcc.as_ref());
+        assert_eq!(
+            body["Headers"],
+            serde_json::json!([{ "Name": "X-Campaign", "Value": "spring" }])
+        );
+        assert_eq!(body["Tag"], "welcome");
+        assert_eq!(
+            body["Metadata"],
+            serde_json::json!({ "subscriber_id": "42" })
+        );
+    }
+
+    #[tokio::test]
+    async fn send_message_encodes_attachments_and_inline_images() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+        let message = EmailMessage::new(&subject(), r#"<img src="cid:logo">"#, &content())
+            .attachment(Attachment::new("issue.pdf", b"%PDF", "application/pdf"))
+            .attachment(Attachment::inline(
+                "logo.png",
+                b"\x89PNG",
+                "image/png",
+                "logo",
+            ));
+
+        Mock::given(path("/email"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_message(EmailKind::Test, &email(), &message)
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        let body = &received_bodies(&mock_server).await[0];
+        assert_eq!(
+            body["Attachments"],
+            serde_json::json!([
+                {
+                    "Name": "issue.pdf",
+                    "Content": "JVBERg==",
+                    "ContentType": "application/pdf",
+                },
+                {
+                    "Name": "logo.png",
+                    "Content": "iVBORw==",
+                    "ContentType": "image/png",
+                    "ContentID": "cid:logo",
+                },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn send_message_batch_sends_the_message_to_every_recipient() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(&mock_server.uri());
+        let recipients = vec![email(), email()];
+        let message = EmailMessage::new(&subject(), &content(), &content())
+            .tag("newsletter")
+            .attachment(Attachment::new("notes.txt", b"hi", "text/plain"));
+
+        Mock::given(path("/email/batch"))
+            .respond_with(AcceptEveryMessage)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client
+            .send_message_batch(EmailKind::Test, &recipients, &message)
+            .await;
+
+        // Assert
+        assert_eq!(outcomes.len(), 2);
+        let body = &received_bodies(&mock_server).await[0];
+        for (sent, recipient) in body.as_array().unwrap().iter().zip(&recipients) {
+            assert_eq!(sent["To"], recipient.as_ref());
+            assert_eq!(sent["Tag"], "newsletter");
+            assert_eq!(sent["Attachments"][0]["Content"], "aGk=");
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::utils::error_chain_fmt;
+
+/// Postmark error code for a recipient that hard bounced or complained before
+const INACTIVE_RECIPIENT: i64 = 406;
+
+/// A message accepted by the email service
+#[derive(Debug, Clone)]
+pub struct SendOutcome {
+    /// Identifier assigned by the email service, if it returned one
+    pub message_id: Option<String>,
+}
+
+/// Why the email service did not accept a message
+#[derive(thiserror::Error)]
+pub enum EmailError {
+    /// The service could not be reached or is overloaded, retrying later may succeed
+    #[error("The email service is temporarily unavailable.")]
+    Transient(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// The recipient can never be mailed and should be suppressed
+    #[error("The email service rejected the recipient: {message} (code {error_code}).")]
+    PermanentRecipient { error_code: i64, message: String },
+    /// The request itself is wrong, e.g. a bad token or sender, and needs attention
+    ///
+    /// `error_code` is None when the service gave no code, e.g. its response was unparsable.
+    #[error(
+        "The email service rejected the request: {message}{}.",
+        error_code.map(|code| format!(" (code {})", code)).unwrap_or_default()
+    )]
+    Configuration {
+        error_code: Option<i64>,
+        message: String,
+    },
+}
+
+impl std::fmt::Debug for EmailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl EmailError {
+    /// Classifies an error reported by the email service
+    pub(super) fn from_provider(error_code: i64, message: String) -> Self {
+        match error_code {
+            INACTIVE_RECIPIENT => Self::PermanentRecipient {
+                error_code,
+                message,
+            },
+            _ => Self::Configuration {
+                error_code: Some(error_code),
+                message,
+            },
+        }
+    }
+
+    /// Wraps a failure to reach the email service
+    pub(super) fn transient(error: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self::Transient(Box::new(error))
+    }
+
+    /// Reports the failure of a whole batch request as a failure of one of its recipients
+    ///
+    /// A rejected request is still a configuration error for each of its recipients.
+    /// Nothing else tells that the recipient can't be mailed, so sending to them again
+    /// later may succeed.
+    pub(super) fn for_batch_recipient(&self) -> Self {
+        match self {
+            Self::Configuration {
+                error_code,
+                message,
+            } => Self::Configuration {
+                error_code: *error_code,
+                message: message.clone(),
+            },
+            Self::Transient(source) => Self::Transient(source.to_string().into()),
+            e @ Self::PermanentRecipient { .. } => Self::Transient(e.to_string().into()),
+        }
+    }
+
+    /// Whether sending the same message again later may succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Transient(_))
+    }
+}
+
+/// Body returned by Postmark for every message, sent alone or in a batch
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(super) struct ProviderResponse {
+    pub error_code: i64,
+    pub message: String,
+    #[serde(rename = "MessageID")]
+    pub message_id: Option<String>,
+}
+
+impl ProviderResponse {
+    /// Converts the response of a single message into its outcome
+    pub(super) fn into_result(self) -> Result<SendOutcome, EmailError> {
+        if self.error_code == 0 {
+            Ok(SendOutcome {
+                message_id: self.message_id,
+            })
+        } else {
+            Err(EmailError::from_provider(self.error_code, self.message))
+        }
+    }
+}
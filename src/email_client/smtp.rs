@@ -0,0 +1,249 @@
+use lettre::{
+    message::{
+        header::{ContentType, HeaderName, HeaderValue},
+        Attachment as MimeAttachment, Mailbox as MimeMailbox, MultiPart,
+    },
+    transport::smtp::{self, authentication::Credentials, client::Tls},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use secrecy::ExposeSecret;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::{EmailError, EmailMessage, SendOutcome};
+use crate::{
+    configuration::SmtpSettings,
+    domain::{Mailbox, SubscriberEmail},
+};
+
+/// SMTP reply codes rejecting the recipient mailbox itself
+const UNKNOWN_MAILBOX_CODES: [u16; 3] = [550, 551, 553];
+
+/// Delivers messages through an SMTP relay instead of the Postmark API
+///
+/// SMTP has no notion of tags or metadata, both are dropped from the messages it sends.
+pub struct SmtpRelay {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpRelay {
+    /// Creates a relay from its settings
+    ///
+    /// # Arguments
+    /// * `conf` - SMTP relay settings
+    /// * `timeout` - Timeout of every SMTP command
+    ///
+    /// # Returns
+    /// A new SmtpRelay if the settings are valid, Error otherwise
+    pub fn new(conf: &SmtpSettings, timeout: Duration) -> Result<Self, smtp::Error> {
+        let mut builder = if conf.starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&conf.host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&conf.host).tls(Tls::None)
+        };
+        if let (Some(username), Some(password)) = (&conf.username, &conf.password) {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                password.expose_secret().to_string(),
+            ));
+        }
+        let transport = builder.port(conf.port).timeout(Some(timeout)).build();
+        Ok(Self { transport })
+    }
+
+    /// Sends a MIME message to the recipients of its envelope
+    pub(super) async fn send(&self, message: Message) -> Result<SendOutcome, EmailError> {
+        let message_id = message
+            .headers()
+            .get_raw("Message-ID")
+            .map(ToString::to_string);
+        match self.transport.send(message).await {
+            Ok(_) => Ok(SendOutcome { message_id }),
+            Err(e) => Err(classify(e)),
+        }
+    }
+}
+
+/// Builds the MIME message sending an email to a recipient
+///
+/// The body is a `multipart/alternative` of the text and HTML content, wrapped in
+/// `multipart/related` when there are inline images and in `multipart/mixed` when
+/// there are attachments.
+pub(super) fn build_message(
+    from: &Mailbox,
+    reply_to: Option<&Mailbox>,
+    recipient: &SubscriberEmail,
+    message: &EmailMessage,
+) -> Result<Message, EmailError> {
+    // Identify the message under the sender's domain, which is also the one it is signed for
+    let domain = from
+        .address()
+        .as_ref()
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.to_string())
+        .unwrap_or_default();
+    let mut builder = Message::builder()
+        .message_id(Some(format!("<{}@{}>", Uuid::new_v4(), domain)))
+        .from(mime_mailbox(from)?)
+        .to(mime_mailbox(&recipient.clone().into())?)
+        .subject(&message.subject);
+    if let Some(reply_to) = reply_to {
+        builder = builder.reply_to(mime_mailbox(reply_to)?);
+    }
+    for cc in &message.cc {
+        builder = builder.cc(mime_mailbox(&cc.clone().into())?);
+    }
+    for bcc in &message.bcc {
+        builder = builder.bcc(mime_mailbox(&bcc.clone().into())?);
+    }
+    for header in &message.headers {
+        if header.value.chars().any(char::is_control) {
+            return Err(invalid(format!("Invalid value for header {}", header.name)));
+        }
+        let name = HeaderName::new_from_ascii(header.name.clone())
+            .map_err(|_| invalid(format!("Invalid header name {}", header.name)))?;
+        builder = builder.raw_header(HeaderValue::new(name, header.value.clone()));
+    }
+
+    let (inline, files): (Vec<_>, Vec<_>) = message
+        .attachments
+        .iter()
+        .partition(|a| a.content_id.is_some());
+
+    let mut body =
+        MultiPart::alternative_plain_html(message.text_body.clone(), message.html_body.clone());
+    if !inline.is_empty() {
+        let mut related = MultiPart::related().multipart(body);
+        for attachment in inline {
+            let content_id = attachment.content_id.clone().unwrap_or_default();
+            related = related.singlepart(
+                MimeAttachment::new_inline(content_id)
+                    .body(attachment.content.clone(), content_type(attachment)?),
+            );
+        }
+        body = related;
+    }
+    if !files.is_empty() {
+        let mut mixed = MultiPart::mixed().multipart(body);
+        for attachment in files {
+            mixed = mixed.singlepart(
+                MimeAttachment::new(attachment.name.clone())
+                    .body(attachment.content.clone(), content_type(attachment)?),
+            );
+        }
+        body = mixed;
+    }
+
+    builder.multipart(body).map_err(|e| invalid(e.to_string()))
+}
+
+/// Converts a mailbox into its lettre counterpart
+fn mime_mailbox(mailbox: &Mailbox) -> Result<MimeMailbox, EmailError> {
+    let address = mailbox
+        .address()
+        .as_ref()
+        .parse()
+        .map_err(|_| invalid(format!("Invalid address {}", mailbox.address())))?;
+    Ok(MimeMailbox::new(
+        mailbox.display_name().map(ToString::to_string),
+        address,
+    ))
+}
+
+/// Parses the MIME type of an attachment
+fn content_type(attachment: &super::Attachment) -> Result<ContentType, EmailError> {
+    ContentType::parse(&attachment.content_type).map_err(|_| {
+        invalid(format!(
+            "Invalid content type {} for {}",
+            attachment.content_type, attachment.name
+        ))
+    })
+}
+
+/// A message that can't be sent as is
+fn invalid(message: String) -> EmailError {
+    EmailError::Configuration {
+        error_code: None,
+        message,
+    }
+}
+
+/// Classifies an error reported by the SMTP relay
+fn classify(error: smtp::Error) -> EmailError {
+    match error.status().map(u16::from) {
+        Some(code) if error.is_permanent() && UNKNOWN_MAILBOX_CODES.contains(&code) => {
+            EmailError::PermanentRecipient {
+                error_code: code.into(),
+                message: error.to_string(),
+            }
+        }
+        Some(code) if error.is_permanent() => EmailError::Configuration {
+            error_code: Some(code.into()),
+            message: error.to_string(),
+        },
+        _ => EmailError::transient(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email_client::Attachment;
+    use claims::{assert_err, assert_ok};
+
+    fn mailbox(s: &str) -> Mailbox {
+        Mailbox::parse(s.to_string()).unwrap()
+    }
+
+    fn recipient() -> SubscriberEmail {
+        SubscriberEmail::parse("ursula@example.com".to_string()).unwrap()
+    }
+
+    #[test]
+    fn a_plain_message_is_a_multipart_alternative() {
+        let message = EmailMessage::new("Subject", "<p>Hi</p>", "Hi");
+        let mime = assert_ok!(build_message(
+            &mailbox("\"Acme News\" <news@acme.io>"),
+            None,
+            &recipient(),
+            &message
+        ));
+        let formatted = String::from_utf8(mime.formatted()).unwrap();
+        assert!(formatted.contains("From: \"Acme News\" <news@acme.io>"));
+        assert!(formatted.contains("@acme.io>\r\n"));
+        assert!(formatted.contains("Message-ID: <"));
+        assert!(formatted.contains("Content-Type: multipart/alternative"));
+        assert!(!formatted.contains("multipart/mixed"));
+    }
+
+    #[test]
+    fn attachments_and_inline_images_are_nested_in_their_own_parts() {
+        let message = EmailMessage::new("Subject", r#"<img src="cid:logo">"#, "Hi")
+            .attachment(Attachment::new("issue.pdf", b"%PDF", "application/pdf"))
+            .attachment(Attachment::inline("logo.png", b"PNG", "image/png", "logo"));
+        let mime = assert_ok!(build_message(
+            &mailbox("news@acme.io"),
+            Some(&mailbox("support@acme.io")),
+            &recipient(),
+            &message
+        ));
+        let formatted = String::from_utf8(mime.formatted()).unwrap();
+        assert!(formatted.contains("Reply-To: support@acme.io"));
+        assert!(formatted.contains("Content-Type: multipart/mixed"));
+        assert!(formatted.contains("Content-Type: multipart/related"));
+        assert!(formatted.contains("Content-ID: <logo>"));
+        assert!(formatted.contains("filename=\"issue.pdf\""));
+    }
+
+    #[test]
+    fn header_values_with_line_breaks_are_rejected() {
+        let message = EmailMessage::new("Subject", "<p>Hi</p>", "Hi")
+            .header("X-Campaign", "spring\r\nBcc: all@acme.io");
+        assert_err!(build_message(
+            &mailbox("news@acme.io"),
+            None,
+            &recipient(),
+            &message
+        ));
+    }
+}
@@ -0,0 +1,99 @@
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use std::{
+    num::{NonZeroU32, NonZeroUsize},
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::{
+    sync::{Semaphore, SemaphorePermit},
+    time::Instant,
+};
+
+/// Longest pause honored from a provider `Retry-After` header
+const MAX_PAUSE: Duration = Duration::from_secs(60);
+
+/// Limits the pace of requests sent to the email provider
+///
+/// The throttle is shared by every clone of an `EmailClient`, so all tasks sending
+/// through the same client draw from the same token bucket and concurrency budget.
+pub struct SendThrottle {
+    rate_limiter: Option<DefaultDirectRateLimiter>,
+    /// Most messages the rate limiter lets through at once, a second's worth
+    burst: NonZeroU32,
+    concurrency: Option<Semaphore>,
+    paused_until: Mutex<Option<Instant>>,
+}
+
+impl SendThrottle {
+    /// Creates a throttle allowing `max_per_second` messages with at most
+    /// `max_concurrency` requests in flight
+    pub fn new(max_per_second: NonZeroU32, max_concurrency: NonZeroUsize) -> Self {
+        Self {
+            rate_limiter: Some(RateLimiter::direct(Quota::per_second(max_per_second))),
+            burst: max_per_second,
+            concurrency: Some(Semaphore::new(max_concurrency.get())),
+            paused_until: Mutex::new(None),
+        }
+    }
+
+    /// Creates a throttle that never delays requests
+    pub fn unlimited() -> Self {
+        Self {
+            rate_limiter: None,
+            burst: NonZeroU32::MAX,
+            concurrency: None,
+            paused_until: Mutex::new(None),
+        }
+    }
+
+    /// Waits until a request carrying `messages` messages may be sent
+    ///
+    /// Every message of a batch request counts against the messages per second limit.
+    ///
+    /// # Returns
+    /// A permit that must be held while the request is in flight
+    pub async fn acquire(&self, messages: u32) -> Option<SemaphorePermit<'_>> {
+        let paused_until = *self.paused_until.lock().expect("Throttle lock poisoned");
+        if let Some(deadline) = paused_until {
+            tokio::time::sleep_until(deadline).await;
+        }
+        if let Some(rate_limiter) = &self.rate_limiter {
+            // The limiter can't hand out more than a burst at once, larger batches wait
+            // for it to refill
+            let mut remaining = messages;
+            while let Some(n) = NonZeroU32::new(remaining.min(self.burst.get())) {
+                rate_limiter
+                    .until_n_ready(n)
+                    .await
+                    .expect("A burst never exceeds the quota");
+                remaining -= n.get();
+            }
+        }
+        match &self.concurrency {
+            Some(semaphore) => Some(
+                semaphore
+                    .acquire()
+                    .await
+                    .expect("Throttle semaphore closed"),
+            ),
+            None => None,
+        }
+    }
+
+    /// Returns how long requests are still held back after a provider `429`
+    pub fn pause_remaining(&self) -> Duration {
+        let paused_until = *self.paused_until.lock().expect("Throttle lock poisoned");
+        paused_until.map_or(Duration::ZERO, |deadline| {
+            deadline.saturating_duration_since(Instant::now())
+        })
+    }
+
+    /// Holds back every request for the given duration, e.g. after a provider `429`
+    pub fn pause_for(&self, duration: Duration) {
+        let deadline = Instant::now() + duration.min(MAX_PAUSE);
+        let mut paused_until = self.paused_until.lock().expect("Throttle lock poisoned");
+        if paused_until.is_none_or(|current| current < deadline) {
+            *paused_until = Some(deadline);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use anyhow::Context;
+use hickory_resolver::{
+    config::{NameServerConfig, ResolverConfig},
+    net::{runtime::TokioRuntimeProvider, NetError},
+    proto::rr::RData,
+    TokioResolver,
+};
+use std::collections::HashSet;
+use tracing::warn;
+
+use crate::{
+    configuration::{DnsCheckSettings, EmailDomainSettings},
+    domain::SubscriberEmail,
+    error_code::ErrorCode,
+};
+
+/// Reason the domain of an email address was rejected
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum DomainRejection {
+    #[error("Addresses from {0} are not accepted, use a permanent email address.")]
+    Disposable(String),
+    #[error("The domain {0} does not exist.")]
+    UnknownDomain(String),
+    #[error("The domain {0} does not accept email.")]
+    Undeliverable(String),
+}
+
+impl DomainRejection {
+    /// Returns the error code of the rejection
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Disposable(_) => ErrorCode::DisposableEmail,
+            Self::UnknownDomain(_) => ErrorCode::UnknownEmailDomain,
+            Self::Undeliverable(_) => ErrorCode::UndeliverableEmail,
+        }
+    }
+}
+
+/// Rejects sign-ups from disposable providers and from domains that can't receive email
+pub struct EmailDomainCheck {
+    blocklist: HashSet<String>,
+    resolver: Option<TokioResolver>,
+}
+
+impl EmailDomainCheck {
+    /// Creates a new domain check from configuration settings
+    ///
+    /// # Returns
+    /// A new EmailDomainCheck if the blocklist file could be read and the resolver
+    /// configured, Error otherwise
+    pub fn new(conf: &EmailDomainSettings) -> anyhow::Result<Self> {
+        let mut blocklist: HashSet<String> = conf.blocklist.iter().map(|d| normalize(d)).collect();
+        if let Some(path) = &conf.blocklist_path {
+            let content = std::fs::read_to_string(path).with_context(|| {
+                format!(
+                    "Failed to read the domain blocklist from {}",
+                    path.display()
+                )
+            })?;
+            blocklist.extend(parse_blocklist(&content));
+        }
+        let resolver = conf.dns.as_ref().map(resolver).transpose()?;
+        Ok(Self {
+            blocklist,
+            resolver,
+        })
+    }
+
+    /// Checks the domain of an email address
+    ///
+    /// Lookups failing for any other reason than the domain or its records not existing
+    /// let the address through, an unreachable nameserver must not block sign-ups.
+    ///
+    /// # Returns
+    /// Ok(()) if the domain is accepted, the reason it was rejected otherwise
+    pub async fn verify(&self, email: &SubscriberEmail) -> Result<(), DomainRejection> {
+        let domain = domain_of(email);
+        if self.is_blocked(domain) {
+            return Err(DomainRejection::Disposable(domain.to_string()));
+        }
+        match &self.resolver {
+            Some(resolver) => check_mail_records(resolver, domain).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Tells whether a domain or one of its parents is blocked
+    fn is_blocked(&self, domain: &str) -> bool {
+        let domain = normalize(domain);
+        std::iter::successors(Some(domain.as_str()), |d| d.split_once('.').map(|(_, p)| p))
+            .any(|d| self.blocklist.contains(d))
+    }
+}
+
+/// Builds a resolver querying the configured nameserver, or the system ones
+fn resolver(conf: &DnsCheckSettings) -> anyhow::Result<TokioResolver> {
+    let mut builder = match conf.nameserver {
+        Some(address) => {
+            let mut nameserver = NameServerConfig::udp_and_tcp(address.ip());
+            for connection in &mut nameserver.connections {
+                connection.port = address.port();
+            }
+            TokioResolver::builder_with_config(
+                ResolverConfig::from_name_servers(vec![nameserver]),
+                TokioRuntimeProvider::default(),
+            )
+        }
+        None => TokioResolver::builder_tokio().context("Failed to read the system DNS settings")?,
+    };
+    builder.options_mut().timeout = conf.timeout();
+    builder.build().context("Failed to build the DNS resolver")
+}
+
+/// Looks up the MX records of a domain, falling back to its addresses as SMTP does
+async fn check_mail_records(resolver: &TokioResolver, domain: &str) -> Result<(), DomainRejection> {
+    // Query the domain as a fully qualified name, skipping the search list
+    let fqdn = format!("{}.", domain);
+    let exchanges = match resolver.mx_lookup(fqdn.as_str()).await {
+        Ok(lookup) => lookup
+            .answers()
+            .iter()
+            .filter_map(|record| match &record.data {
+                RData::MX(mx) => Some(mx.exchange.clone()),
+                _ => None,
+            })
+            .collect(),
+        Err(e) if e.is_nx_domain() => {
+            return Err(DomainRejection::UnknownDomain(domain.to_string()))
+        }
+        Err(e) if e.is_no_records_found() => Vec::new(),
+        Err(e) => return lookup_failed(domain, e),
+    };
+    // A single "." exchange is a null MX, RFC 7505
+    if exchanges.iter().any(|exchange| exchange.is_root()) {
+        return Err(DomainRejection::Undeliverable(domain.to_string()));
+    }
+    if !exchanges.is_empty() {
+        return Ok(());
+    }
+
+    match resolver.lookup_ip(fqdn.as_str()).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.is_nx_domain() => Err(DomainRejection::UnknownDomain(domain.to_string())),
+        Err(e) if e.is_no_records_found() => {
+            Err(DomainRejection::Undeliverable(domain.to_string()))
+        }
+        Err(e) => lookup_failed(domain, e),
+    }
+}
+
+fn lookup_failed(domain: &str, e: NetError) -> Result<(), DomainRejection> {
+    warn!(error = %e, "Failed to look up the mail servers of {}", domain);
+    Ok(())
+}
+
+/// Returns the ASCII domain of an email address
+fn domain_of(email: &SubscriberEmail) -> &str {
+    email
+        .normalized()
+        .rsplit_once('@')
+        .map_or("", |(_, domain)| domain)
+}
+
+fn normalize(domain: &str) -> String {
+    domain.trim().trim_end_matches('.').to_lowercase()
+}
+
+/// Parses a blocklist file, one domain per line, `#` starting a comment
+fn parse_blocklist(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .lines()
+        .map(|line| line.split_once('#').map_or(line, |(domain, _)| domain))
+        .map(normalize)
+        .filter(|domain| !domain.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok};
+
+    fn check(blocklist: &[&str]) -> EmailDomainCheck {
+        EmailDomainCheck::new(&EmailDomainSettings {
+            blocklist: blocklist.iter().map(ToString::to_string).collect(),
+            blocklist_path: None,
+            dns: None,
+        })
+        .unwrap()
+    }
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn blocked_domains_are_rejected_regardless_of_case() {
+        let check = check(&["Mailinator.com"]);
+        assert_eq!(
+            check.verify(&email("ursula@MAILINATOR.com")).await,
+            Err(DomainRejection::Disposable("mailinator.com".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn subdomains_of_blocked_domains_are_rejected() {
+        let check = check(&["mailinator.com"]);
+        assert_err!(check.verify(&email("ursula@eu.mailinator.com")).await);
+    }
+
+    #[tokio::test]
+    async fn domains_merely_ending_like_a_blocked_one_are_accepted() {
+        let check = check(&["mailinator.com"]);
+        assert_ok!(check.verify(&email("ursula@notmailinator.com")).await);
+    }
+
+    #[tokio::test]
+    async fn unlisted_domains_are_accepted_without_a_dns_check() {
+        let check = check(&["mailinator.com"]);
+        assert_ok!(check.verify(&email("ursula@example.com")).await);
+    }
+
+    #[test]
+    fn blocklist_files_skip_comments_and_blank_lines() {
+        let content = "# Disposable providers\nyopmail.com\n\n  Trashmail.de.  # and its aliases\n";
+        let domains: Vec<String> = parse_blocklist(content).collect();
+        assert_eq!(domains, vec!["yopmail.com", "trashmail.de"]);
+    }
+}
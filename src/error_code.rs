@@ -0,0 +1,152 @@
+use serde::{Serialize, Serializer};
+use strum::{EnumIter, IntoEnumIterator};
+use utoipa::{
+    openapi::{schema::ObjectBuilder, RefOr, Schema, Type},
+    PartialSchema, ToSchema,
+};
+
+/// Stable, machine-readable codes of the errors returned by the API
+///
+/// Clients key localized messages on these codes, they must never change meaning. They are
+/// documented in the order they are declared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter)]
+pub enum ErrorCode {
+    InvalidRequest,
+    MalformedRequest,
+    UnsupportedMediaType,
+    MissingField,
+    InvalidEmail,
+    DisposableEmail,
+    UnknownEmailDomain,
+    UndeliverableEmail,
+    EmptyName,
+    NameTooLong,
+    ForbiddenNameCharacter,
+    InvalidMailbox,
+    EmptyReason,
+    ChallengeFailed,
+    CsrfRejected,
+    RateLimited,
+    UnknownToken,
+    ExpiredToken,
+    Unauthorized,
+    Forbidden,
+    SuppressionNotFound,
+    EmptyApiKeyName,
+    EmptyScopes,
+    UnknownScope,
+    ApiKeyNotFound,
+    EmptyUsername,
+    PasswordTooShort,
+    UnknownRole,
+    UsernameTaken,
+    UserNotFound,
+    NewsletterNotDelivered,
+    Internal,
+}
+
+impl ErrorCode {
+    /// Returns the code as sent to clients
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest => "request.invalid",
+            Self::MalformedRequest => "request.malformed",
+            Self::UnsupportedMediaType => "request.unsupported_media_type",
+            Self::MissingField => "field.missing",
+            Self::InvalidEmail => "email.invalid",
+            Self::DisposableEmail => "email.disposable",
+            Self::UnknownEmailDomain => "email.domain_unknown",
+            Self::UndeliverableEmail => "email.undeliverable",
+            Self::EmptyName => "name.empty",
+            Self::NameTooLong => "name.too_long",
+            Self::ForbiddenNameCharacter => "name.forbidden_character",
+            Self::InvalidMailbox => "mailbox.invalid",
+            Self::EmptyReason => "reason.empty",
+            Self::ChallengeFailed => "challenge.failed",
+            Self::CsrfRejected => "csrf.rejected",
+            Self::RateLimited => "rate_limit.exceeded",
+            Self::UnknownToken => "token.unknown",
+            Self::ExpiredToken => "token.expired",
+            Self::Unauthorized => "auth.unauthorized",
+            Self::Forbidden => "auth.forbidden",
+            Self::SuppressionNotFound => "suppression.not_found",
+            Self::EmptyApiKeyName => "api_key.name_empty",
+            Self::EmptyScopes => "api_key.scopes_empty",
+            Self::UnknownScope => "api_key.scope_unknown",
+            Self::ApiKeyNotFound => "api_key.not_found",
+            Self::EmptyUsername => "user.username_empty",
+            Self::PasswordTooShort => "user.password_too_short",
+            Self::UnknownRole => "user.role_unknown",
+            Self::UsernameTaken => "user.username_taken",
+            Self::UserNotFound => "user.not_found",
+            Self::NewsletterNotDelivered => "newsletter.not_delivered",
+            Self::Internal => "internal",
+        }
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl PartialSchema for ErrorCode {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .description(Some("Stable, machine-readable code of the error"))
+            .enum_values(Some(Self::iter().map(|code| code.as_str())))
+            .into()
+    }
+}
+
+impl ToSchema for ErrorCode {}
+
+/// A request field that failed validation
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct FieldError {
+    pub field: String,
+    pub code: ErrorCode,
+    pub detail: String,
+}
+
+impl FieldError {
+    /// Creates a new field error
+    pub fn new(field: impl Into<String>, code: ErrorCode, detail: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            code,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Every field of a request that failed validation
+#[derive(Clone, Debug)]
+pub struct ValidationErrors(pub Vec<FieldError>);
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let details: Vec<&str> = self.0.iter().map(|e| e.detail.as_str()).collect();
+        write!(f, "{}", details.join(" "))
+    }
+}
+
+impl From<FieldError> for ValidationErrors {
+    fn from(error: FieldError) -> Self {
+        Self(vec![error])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn codes_sent_to_clients_are_unique() {
+        let codes: HashSet<&str> = ErrorCode::iter().map(|code| code.as_str()).collect();
+        assert_eq!(codes.len(), ErrorCode::iter().count());
+    }
+}
@@ -0,0 +1,210 @@
+use axum::{
+    extract::{
+        rejection::{FormRejection, JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts, Request,
+    },
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{error, instrument, warn};
+
+use crate::{
+    error_code::{ErrorCode, FieldError, ValidationErrors},
+    router::ErrorResponse,
+    utils::error_chain_fmt,
+};
+
+/// Extracts a URL encoded form body, see [`axum::Form`]
+pub struct Form<T>(pub T);
+
+impl<T, S> FromRequest<S> for Form<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ExtractionRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Form(value) = axum::Form::<T>::from_request(req, state).await?;
+        Ok(Self(value))
+    }
+}
+
+/// Extracts a JSON body or responds with one, see [`axum::Json`]
+pub struct Json<T>(pub T);
+
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ExtractionRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Json(value) = axum::Json::<T>::from_request(req, state).await?;
+        Ok(Self(value))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// Extracts the query string, see [`axum::extract::Query`]
+pub struct Query<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ExtractionRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Query(value) =
+            axum::extract::Query::<T>::from_request_parts(parts, state).await?;
+        Ok(Self(value))
+    }
+}
+
+/// Extracts path parameters, see [`axum::extract::Path`]
+#[derive(Debug)]
+pub struct Path<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ExtractionRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Path(value) =
+            axum::extract::Path::<T>::from_request_parts(parts, state).await?;
+        Ok(Self(value))
+    }
+}
+
+/// Extracts a request body sent either as a URL encoded form or as JSON
+///
+/// The decoder is picked from the `Content-Type` header, any other media type is
+/// rejected with `415 Unsupported Media Type`.
+pub struct FormOrJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for FormOrJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ExtractionRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        if content_type == "application/json" || content_type.ends_with("+json") {
+            let Json(value) = Json::<T>::from_request(req, state).await?;
+            Ok(Self(value))
+        } else if content_type == "application/x-www-form-urlencoded" {
+            let Form(value) = Form::<T>::from_request(req, state).await?;
+            Ok(Self(value))
+        } else {
+            Err(ExtractionRejection::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Expected a request with `Content-Type: application/json` or \
+                `application/x-www-form-urlencoded`."
+                    .to_string(),
+            ))
+        }
+    }
+}
+
+/// Rejection of a request whose body, form, query string or path can't be extracted
+#[derive(thiserror::Error)]
+#[error("{detail}")]
+pub struct ExtractionRejection {
+    status: StatusCode,
+    detail: String,
+}
+
+impl ExtractionRejection {
+    fn new(status: StatusCode, detail: String) -> Self {
+        Self { status, detail }
+    }
+
+    /// Names the field serde reported as missing, if any
+    fn missing_field(&self) -> Option<&str> {
+        let (_, rest) = self.detail.split_once("missing field `")?;
+        rest.split_once('`').map(|(field, _)| field)
+    }
+}
+
+impl std::fmt::Debug for ExtractionRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl From<JsonRejection> for ExtractionRejection {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<FormRejection> for ExtractionRejection {
+    fn from(rejection: FormRejection) -> Self {
+        Self::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ExtractionRejection {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ExtractionRejection {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl IntoResponse for ExtractionRejection {
+    #[instrument(skip_all)]
+    fn into_response(self) -> Response {
+        // Determine the appropriate error code.
+        let code = match self.status {
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ErrorCode::UnsupportedMediaType,
+            StatusCode::UNPROCESSABLE_ENTITY => ErrorCode::InvalidRequest,
+            status if status.is_server_error() => ErrorCode::Internal,
+            _ => ErrorCode::MalformedRequest,
+        };
+
+        // Create the error response body
+        let mut body = ErrorResponse::new(self.status, code, self.detail.clone());
+        if let Some(field) = self.missing_field() {
+            body = body.with_errors(ValidationErrors::from(FieldError::new(
+                field,
+                ErrorCode::MissingField,
+                format!("The field {} is missing.", field),
+            )));
+        }
+
+        // Log the error
+        if self.status.is_server_error() {
+            error!("{:?}", self);
+        } else {
+            warn!("{:?}", self);
+        }
+
+        body.into_response()
+    }
+}
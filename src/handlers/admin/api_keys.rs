@@ -0,0 +1,155 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    api_keys::{self, ApiKey, Scope},
+    error_code::{ErrorCode, FieldError, ValidationErrors},
+    extract::{Json, Path},
+    router::{AppState, ErrorResponse},
+    utils::error_chain_fmt,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/admin/api_keys", get(list_api_keys).post(create_api_key))
+        .route("/admin/api_keys/{id}", delete(revoke_api_key))
+}
+
+#[derive(Deserialize)]
+pub struct ApiKeyData {
+    name: String,
+    scopes: Vec<String>,
+}
+
+impl ApiKeyData {
+    /// Validates the name and the scopes, reporting all of the invalid fields at once
+    fn parse(&self) -> Result<(&str, Vec<Scope>), ValidationErrors> {
+        let mut errors = Vec::new();
+        let name = self.name.trim();
+        if name.is_empty() {
+            errors.push(FieldError::new(
+                "name",
+                ErrorCode::EmptyApiKeyName,
+                "The API key name cannot be empty.",
+            ));
+        }
+        if self.scopes.is_empty() {
+            errors.push(FieldError::new(
+                "scopes",
+                ErrorCode::EmptyScopes,
+                "The API key must be granted at least one scope.",
+            ));
+        }
+        let mut scopes = Vec::with_capacity(self.scopes.len());
+        for scope in &self.scopes {
+            match Scope::parse(scope) {
+                Some(scope) => scopes.push(scope),
+                None => errors.push(FieldError::new(
+                    "scopes",
+                    ErrorCode::UnknownScope,
+                    format!("The scope {} is unknown.", scope),
+                )),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ValidationErrors(errors));
+        }
+        Ok((name, scopes))
+    }
+}
+
+/// A newly created API key, the only response carrying the key itself
+#[derive(Serialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    api_key: ApiKey,
+    key: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum ApiKeyAdminError {
+    #[error("{0}")]
+    ValidationError(ValidationErrors),
+    #[error("There is no active API key with this id.")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ApiKeyAdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for ApiKeyAdminError {
+    #[instrument(skip_all)]
+    fn into_response(self) -> Response {
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = match self {
+            Self::ValidationError(_) => (StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest),
+            Self::NotFound => (StatusCode::NOT_FOUND, ErrorCode::ApiKeyNotFound),
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
+        };
+
+        // Create the error response body
+        let mut body = ErrorResponse::new(status_code, code, self.to_string());
+        if let Self::ValidationError(errors) = &self {
+            body = body.with_errors(errors.clone());
+        }
+
+        // Log the error
+        match self {
+            Self::ValidationError(_) | Self::NotFound => warn!("{:?}", self),
+            Self::UnexpectedError(e) => error!("{:?}", e),
+        }
+
+        body.into_response()
+    }
+}
+
+#[instrument(name = "List API keys", skip_all)]
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ApiKey>>, ApiKeyAdminError> {
+    let api_keys = api_keys::list_api_keys(&state.db)
+        .await
+        .context("Failed to fetch the API keys.")?;
+    Ok(Json(api_keys))
+}
+
+/// Creates an API key, returning the key itself this one time only
+#[instrument(name = "Create an API key", skip_all, fields(name = data.name))]
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    Json(data): Json<ApiKeyData>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), ApiKeyAdminError> {
+    let (name, scopes) = data.parse().map_err(ApiKeyAdminError::ValidationError)?;
+    let (api_key, key) = api_keys::create_api_key(&state.db, name, &scopes)
+        .await
+        .context("Failed to store the API key.")?;
+    Ok((StatusCode::CREATED, Json(CreatedApiKey { api_key, key })))
+}
+
+#[instrument(name = "Revoke an API key", skip(state))]
+pub async fn revoke_api_key(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ApiKeyAdminError> {
+    let revoked = api_keys::revoke_api_key(&state.db, id)
+        .await
+        .context("Failed to revoke the API key.")?;
+    if !revoked {
+        return Err(ApiKeyAdminError::NotFound);
+    }
+    Ok(StatusCode::OK)
+}
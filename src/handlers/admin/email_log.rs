@@ -0,0 +1,123 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::{
+    domain::SubscriberEmail,
+    error_code::ErrorCode,
+    extract::{Json, Query},
+    router::{AppState, DbPool, ErrorResponse},
+    utils::error_chain_fmt,
+};
+
+/// Number of entries returned when the query does not set a limit
+const DEFAULT_LIMIT: i64 = 100;
+
+/// Maximum number of entries returned by a single query
+const MAX_LIMIT: i64 = 1000;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/admin/email_log", get(query_email_log))
+}
+
+#[derive(Deserialize)]
+pub struct Parameters {
+    /// Matches the address whatever its case
+    recipient: Option<String>,
+    kind: Option<String>,
+    issue_id: Option<Uuid>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct EmailLogEntry {
+    id: Uuid,
+    recipient: String,
+    kind: String,
+    issue_id: Option<Uuid>,
+    provider_message_id: Option<String>,
+    status: String,
+    latency_millis: i32,
+    error: Option<String>,
+    sent_at: DateTime<Utc>,
+}
+
+#[derive(thiserror::Error)]
+pub enum EmailLogError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for EmailLogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for EmailLogError {
+    #[instrument(skip_all)]
+    fn into_response(self) -> Response {
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = match self {
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
+        };
+
+        // Create the error response body
+        let body = ErrorResponse::new(status_code, code, self.to_string());
+
+        // Log the error
+        match self {
+            Self::UnexpectedError(e) => error!("{:?}", e),
+        }
+
+        body.into_response()
+    }
+}
+
+/// Lists the most recent deliveries, optionally filtered by recipient, kind or issue
+#[instrument(name = "Query the email delivery log", skip_all)]
+pub async fn query_email_log(
+    State(state): State<AppState>,
+    Query(parameters): Query<Parameters>,
+) -> Result<Json<Vec<EmailLogEntry>>, EmailLogError> {
+    let entries = get_email_log_entries(&state.db, parameters)
+        .await
+        .context("Failed to query the email delivery log.")?;
+    Ok(Json(entries))
+}
+
+#[instrument(name = "Get email log entries", skip_all)]
+async fn get_email_log_entries(
+    pool: &DbPool,
+    parameters: Parameters,
+) -> Result<Vec<EmailLogEntry>, sqlx::Error> {
+    let limit = parameters
+        .limit
+        .unwrap_or(DEFAULT_LIMIT)
+        .clamp(1, MAX_LIMIT);
+    sqlx::query_as!(
+        EmailLogEntry,
+        r#"SELECT id, recipient, kind, issue_id, provider_message_id, status, latency_millis, error, sent_at
+        FROM email_log
+        WHERE ($1::text IS NULL OR recipient_normalized = $1)
+        AND ($2::text IS NULL OR kind = $2)
+        AND ($3::uuid IS NULL OR issue_id = $3)
+        ORDER BY sent_at DESC
+        LIMIT $4"#,
+        parameters.recipient.as_deref().map(SubscriberEmail::normalize),
+        parameters.kind,
+        parameters.issue_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
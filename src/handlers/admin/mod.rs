@@ -0,0 +1,41 @@
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use crate::{
+    authentication,
+    handlers::{newsletters, subscribers},
+    router::AppState,
+    users::Permission,
+};
+
+pub mod api_keys;
+pub mod email_log;
+pub mod suppressions;
+pub mod users;
+
+/// Routes reserved for administrators, mounted behind admin authentication
+///
+/// Each group of routes requires a permission, granted by the role of the admin user.
+pub fn router() -> Router<AppState> {
+    let require = |permission| {
+        axum::middleware::from_fn_with_state(permission, authentication::require_permission)
+    };
+
+    // Staff publish and browse subscribers with their own credentials rather than API keys
+    let newsletters_router = Router::new()
+        .route("/admin/newsletters", post(newsletters::publish_newsletter))
+        .route_layer(require(Permission::PublishNewsletters));
+    let subscribers_router = Router::new()
+        .route("/admin/subscribers", get(subscribers::list_subscribers))
+        .route_layer(require(Permission::ManageSubscribers));
+
+    Router::new()
+        .merge(users::router().route_layer(require(Permission::ManageUsers)))
+        .merge(api_keys::router().route_layer(require(Permission::ManageApiKeys)))
+        .merge(newsletters_router)
+        .merge(email_log::router().route_layer(require(Permission::ReadStats)))
+        .merge(subscribers_router)
+        .merge(suppressions::router().route_layer(require(Permission::ManageSubscribers)))
+}
@@ -0,0 +1,128 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Router,
+};
+use serde::Deserialize;
+use tracing::{error, instrument, warn};
+
+use crate::{
+    domain::SubscriberEmail,
+    error_code::{ErrorCode, FieldError, ValidationErrors},
+    extract::{Json, Path},
+    router::{AppState, ErrorResponse},
+    suppression::{self, Suppression},
+    utils::error_chain_fmt,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/admin/suppressions",
+            get(list_suppressions).post(add_suppression),
+        )
+        .route("/admin/suppressions/{email}", delete(remove_suppression))
+}
+
+#[derive(Deserialize)]
+pub struct SuppressionData {
+    email: String,
+    reason: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum SuppressionError {
+    #[error("{0}")]
+    ValidationError(ValidationErrors),
+    #[error("The email address is not on the suppression list.")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SuppressionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for SuppressionError {
+    #[instrument(skip_all)]
+    fn into_response(self) -> Response {
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = match self {
+            Self::ValidationError(_) => (StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest),
+            Self::NotFound => (StatusCode::NOT_FOUND, ErrorCode::SuppressionNotFound),
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
+        };
+
+        // Create the error response body
+        let mut body = ErrorResponse::new(status_code, code, self.to_string());
+        if let Self::ValidationError(errors) = &self {
+            body = body.with_errors(errors.clone());
+        }
+
+        // Log the error
+        match self {
+            Self::ValidationError(_) | Self::NotFound => warn!("{:?}", self),
+            Self::UnexpectedError(e) => error!("{:?}", e),
+        }
+
+        body.into_response()
+    }
+}
+
+#[instrument(name = "List suppressed addresses", skip_all)]
+pub async fn list_suppressions(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Suppression>>, SuppressionError> {
+    let suppressions = suppression::list_suppressions(&state.db)
+        .await
+        .context("Failed to fetch the suppression list.")?;
+    Ok(Json(suppressions))
+}
+
+#[instrument(name = "Suppress an address", skip_all, fields(email = data.email))]
+pub async fn add_suppression(
+    State(state): State<AppState>,
+    Json(data): Json<SuppressionData>,
+) -> Result<StatusCode, SuppressionError> {
+    let email = SubscriberEmail::parse(data.email).map_err(|e| {
+        SuppressionError::ValidationError(
+            FieldError::new("email", ErrorCode::InvalidEmail, e).into(),
+        )
+    })?;
+    let reason = data.reason.trim();
+    if reason.is_empty() {
+        return Err(SuppressionError::ValidationError(
+            FieldError::new(
+                "reason",
+                ErrorCode::EmptyReason,
+                "Suppression reason cannot be empty",
+            )
+            .into(),
+        ));
+    }
+
+    suppression::suppress(&state.db, email.as_ref(), reason, "admin")
+        .await
+        .context("Failed to add the address to the suppression list.")?;
+    Ok(StatusCode::OK)
+}
+
+#[instrument(name = "Remove a suppressed address", skip_all, fields(email = email))]
+pub async fn remove_suppression(
+    State(state): State<AppState>,
+    Path(email): Path<String>,
+) -> Result<StatusCode, SuppressionError> {
+    let removed = suppression::unsuppress(&state.db, &email)
+        .await
+        .context("Failed to remove the address from the suppression list.")?;
+    if !removed {
+        return Err(SuppressionError::NotFound);
+    }
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,155 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Router,
+};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use tracing::{error, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    error_code::{ErrorCode, FieldError, ValidationErrors},
+    extract::{Json, Path},
+    router::{AppState, ErrorResponse},
+    users::{self, Role, User},
+    utils::error_chain_fmt,
+};
+
+/// Minimum number of characters of a password
+const MIN_PASSWORD_LENGTH: usize = 12;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/admin/users", get(list_users).post(create_user))
+        .route("/admin/users/{user_id}", delete(delete_user))
+}
+
+#[derive(Deserialize)]
+pub struct UserData {
+    username: String,
+    password: SecretString,
+    role: String,
+}
+
+impl UserData {
+    /// Validates the username, the password and the role, reporting all of the invalid
+    /// fields at once
+    fn parse(&self) -> Result<(&str, Role), ValidationErrors> {
+        let mut errors = Vec::new();
+        let username = self.username.trim();
+        if username.is_empty() {
+            errors.push(FieldError::new(
+                "username",
+                ErrorCode::EmptyUsername,
+                "The username cannot be empty.",
+            ));
+        }
+        if self.password.expose_secret().chars().count() < MIN_PASSWORD_LENGTH {
+            errors.push(FieldError::new(
+                "password",
+                ErrorCode::PasswordTooShort,
+                format!(
+                    "The password must be at least {} characters long.",
+                    MIN_PASSWORD_LENGTH
+                ),
+            ));
+        }
+        let role = Role::parse(&self.role);
+        if role.is_none() {
+            errors.push(FieldError::new(
+                "role",
+                ErrorCode::UnknownRole,
+                format!("The role {} is unknown.", self.role),
+            ));
+        }
+        match role {
+            Some(role) if errors.is_empty() => Ok((username, role)),
+            _ => Err(ValidationErrors(errors)),
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum UserAdminError {
+    #[error("{0}")]
+    ValidationError(ValidationErrors),
+    #[error("A user with this username already exists.")]
+    UsernameTaken,
+    #[error("There is no user with this id.")]
+    NotFound,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for UserAdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for UserAdminError {
+    #[instrument(skip_all)]
+    fn into_response(self) -> Response {
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = match self {
+            Self::ValidationError(_) => (StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest),
+            Self::UsernameTaken => (StatusCode::CONFLICT, ErrorCode::UsernameTaken),
+            Self::NotFound => (StatusCode::NOT_FOUND, ErrorCode::UserNotFound),
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
+        };
+
+        // Create the error response body
+        let mut body = ErrorResponse::new(status_code, code, self.to_string());
+        if let Self::ValidationError(errors) = &self {
+            body = body.with_errors(errors.clone());
+        }
+
+        // Log the error
+        match self {
+            Self::ValidationError(_) | Self::UsernameTaken | Self::NotFound => {
+                warn!("{:?}", self)
+            }
+            Self::UnexpectedError(e) => error!("{:?}", e),
+        }
+
+        body.into_response()
+    }
+}
+
+#[instrument(name = "List users", skip_all)]
+pub async fn list_users(State(state): State<AppState>) -> Result<Json<Vec<User>>, UserAdminError> {
+    let users = users::list_users(&state.db)
+        .await
+        .context("Failed to fetch the users.")?;
+    Ok(Json(users))
+}
+
+#[instrument(name = "Create a user", skip_all, fields(username = data.username, role = data.role))]
+pub async fn create_user(
+    State(state): State<AppState>,
+    Json(data): Json<UserData>,
+) -> Result<(StatusCode, Json<User>), UserAdminError> {
+    let (username, role) = data.parse().map_err(UserAdminError::ValidationError)?;
+    let user = users::create_user(&state.db, username, data.password.clone(), role)
+        .await?
+        .ok_or(UserAdminError::UsernameTaken)?;
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+#[instrument(name = "Delete a user", skip(state))]
+pub async fn delete_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+) -> Result<StatusCode, UserAdminError> {
+    let deleted = users::delete_user(&state.db, user_id)
+        .await
+        .context("Failed to delete the user.")?;
+    if !deleted {
+        return Err(UserAdminError::NotFound);
+    }
+    Ok(StatusCode::OK)
+}
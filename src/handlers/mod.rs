@@ -1,4 +1,9 @@
+pub mod admin;
 pub mod health_check;
 pub mod newsletters;
+pub mod openapi;
+pub mod subscribe_form;
+pub mod subscribers;
 pub mod subscriptions;
 pub mod subscriptions_confirm;
+pub mod webhooks;
@@ -4,13 +4,19 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
     routing::post,
-    Json, Router,
+    Router,
 };
 use tracing::{error, instrument, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
 
 use crate::{
-    domain::SubscriberEmail,
+    domain::{Mailbox, SubscriberEmail},
+    email_client::{EmailError, EmailKind, EmailMessage},
+    error_code::{ErrorCode, FieldError, ValidationErrors},
+    extract::Json,
     router::{AppState, DbPool, ErrorResponse},
+    suppression,
     utils::error_chain_fmt,
 };
 
@@ -18,20 +24,53 @@ pub fn router() -> Router<AppState> {
     Router::new().route("/newsletters", post(publish_newsletter))
 }
 
-#[derive(serde::Deserialize)]
+/// A newsletter issue sent to every confirmed subscriber
+#[derive(serde::Deserialize, ToSchema)]
 pub struct BodyData {
     title: String,
     content: Content,
+    /// Overrides the configured sender for this issue
+    from: Option<String>,
+    /// Overrides the configured reply-to mailbox for this issue
+    reply_to: Option<String>,
 }
 
-#[derive(serde::Deserialize)]
+impl BodyData {
+    /// Builds the message sent to every subscriber
+    fn message(&self) -> Result<EmailMessage, FieldError> {
+        let mailbox = |field, value: &String| {
+            Mailbox::parse(value.clone())
+                .map_err(|e| FieldError::new(field, ErrorCode::InvalidMailbox, e))
+        };
+        let mut message = EmailMessage::new(&self.title, &self.content.html, &self.content.text);
+        if let Some(from) = &self.from {
+            message = message.from(mailbox("from", from)?);
+        }
+        if let Some(reply_to) = &self.reply_to {
+            message = message.reply_to(mailbox("reply_to", reply_to)?);
+        }
+        Ok(message)
+    }
+}
+
+#[derive(serde::Deserialize, ToSchema)]
 pub struct Content {
     text: String,
     html: String,
 }
 
+/// A published issue, its id filters the admin email log
+#[derive(serde::Serialize, ToSchema)]
+pub struct PublishResponse {
+    issue_id: Uuid,
+}
+
 #[derive(thiserror::Error)]
 pub enum PublishError {
+    #[error("{0}")]
+    ValidationError(ValidationErrors),
+    #[error("The email service accepted no message of issue {0}, see the email log.")]
+    NotDelivered(Uuid),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -45,44 +84,71 @@ impl std::fmt::Debug for PublishError {
 impl IntoResponse for PublishError {
     #[instrument(skip_all)]
     fn into_response(self) -> Response {
-        // Determine the appropriate status code.
-        let status_code = match self {
-            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = match self {
+            Self::ValidationError(_) => (StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest),
+            Self::NotDelivered(_) => (StatusCode::BAD_GATEWAY, ErrorCode::NewsletterNotDelivered),
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
         };
 
         // Create the error response body
-        let body = ErrorResponse::new(status_code.as_u16(), self.to_string());
+        let mut body = ErrorResponse::new(status_code, code, self.to_string());
+        if let Self::ValidationError(errors) = &self {
+            body = body.with_errors(errors.clone());
+        }
 
         // Log the error
         match self {
+            Self::ValidationError(e) => warn!("{:?}", e),
+            e @ Self::NotDelivered(_) => error!("{:?}", e),
             Self::UnexpectedError(e) => error!("{:?}", e),
         }
 
-        (status_code, Json(body)).into_response()
+        body.into_response()
     }
 }
 
+/// Sends a newsletter issue to every confirmed subscriber
+///
+/// Messages go out at the configured messages per second, the request lasts until
+/// the whole issue was sent.
+#[utoipa::path(
+    post,
+    path = "/newsletters",
+    tag = "newsletters",
+    request_body = BodyData,
+    security(("api_key" = ["newsletters:publish"])),
+    responses(
+        (status = 200, description = "The issue was sent", body = PublishResponse),
+        (status = 400, description = "Invalid sender or reply-to mailbox", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The API key lacks the `newsletters:publish` scope", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 415, description = "Unsupported body media type", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Missing or mistyped field", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 502, description = "The email service accepted no message of the issue", body = ErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
 pub async fn publish_newsletter(
     State(state): State<AppState>,
     Json(body): Json<BodyData>,
-) -> Result<StatusCode, PublishError> {
+) -> Result<Json<PublishResponse>, PublishError> {
+    let message = body
+        .message()
+        .map_err(|e| PublishError::ValidationError(e.into()))?;
+    if let Some(from) = message.sender() {
+        // A per-issue sender must pass DMARC like the configured one
+        state.email_client.check_sender(from).map_err(|e| {
+            PublishError::ValidationError(
+                FieldError::new("from", ErrorCode::InvalidMailbox, e).into(),
+            )
+        })?;
+    }
     let subscribers = get_confirmed_subscribers(&state.db).await?;
+    let mut recipients = Vec::with_capacity(subscribers.len());
     for subscriber in subscribers {
         match subscriber {
-            Ok(subscriber) => {
-                state
-                    .email_client
-                    .send_email(
-                        &subscriber.email,
-                        &body.title,
-                        &body.content.html,
-                        &body.content.text,
-                    )
-                    .await
-                    .with_context(|| {
-                        format!("Failed to send newsletter issue to {}", subscriber.email)
-                    })?;
-            }
+            Ok(subscriber) => recipients.push(subscriber.email),
             Err(error) => {
                 warn!(
                     error.cause_chain = ?error,
@@ -92,7 +158,51 @@ pub async fn publish_newsletter(
             }
         }
     }
-    Ok(StatusCode::OK)
+
+    let issue_id = Uuid::new_v4();
+    let outcomes = state
+        .email_client
+        .send_message_batch(EmailKind::Newsletter { issue_id }, &recipients, &message)
+        .await;
+    let (mut accepted, mut failed) = (0, 0);
+    for outcome in outcomes {
+        match outcome.result {
+            Ok(_) => accepted += 1,
+            Err(error @ EmailError::PermanentRecipient { .. }) => {
+                warn!(
+                    error.cause_chain = ?error,
+                    "Suppressing {}, the email service rejected it", outcome.recipient
+                );
+                suppression::suppress(
+                    &state.db,
+                    outcome.recipient.as_ref(),
+                    "inactive_recipient",
+                    "postmark",
+                )
+                .await
+                .context("Failed to suppress a rejected recipient")?;
+            }
+            Err(error) if error.is_transient() => {
+                failed += 1;
+                warn!(
+                    error.cause_chain = ?error,
+                    "Failed to deliver newsletter issue to {}", outcome.recipient
+                );
+            }
+            Err(error) => {
+                failed += 1;
+                error!(
+                    error.cause_chain = ?error,
+                    "Failed to deliver newsletter issue to {}", outcome.recipient
+                );
+            }
+        }
+    }
+    // Recipients rejected as inactive are expected, they don't tell that the service failed
+    if accepted == 0 && failed > 0 {
+        return Err(PublishError::NotDelivered(issue_id));
+    }
+    Ok(Json(PublishResponse { issue_id }))
 }
 
 struct ConfirmedSubscriber {
@@ -104,9 +214,12 @@ async fn get_confirmed_subscribers(
     pool: &DbPool,
 ) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
     let confirmed_subscribers =
-        sqlx::query!(r#"SELECT email FROM subscriptions WHERE status = 'confirmed'"#)
-            .fetch_all(pool)
-            .await?
+        sqlx::query!(
+            r#"SELECT email FROM subscriptions WHERE status = 'confirmed'
+            AND NOT EXISTS (SELECT 1 FROM suppressions WHERE suppressions.email = subscriptions.email_normalized)"#
+        )
+        .fetch_all(pool)
+        .await?
             .into_iter()
             .map(|r| match SubscriberEmail::parse(r.email) {
                 Ok(email) => Ok(ConfirmedSubscriber { email }),
@@ -0,0 +1,26 @@
+use axum::{response::Html, routing::get, Router};
+use utoipa::OpenApi;
+use utoipa_redoc::Redoc;
+
+use crate::{extract::Json, openapi::ApiDoc, router::AppState};
+
+/// Path of the OpenAPI document
+const SPEC_PATH: &str = "/openapi.json";
+
+/// Serves the OpenAPI document, along with a Redoc UI rendering it when enabled
+pub fn router(ui: bool) -> Router<AppState> {
+    let router = Router::new().route(SPEC_PATH, get(openapi_json));
+    if ui {
+        router.route("/docs", get(docs))
+    } else {
+        router
+    }
+}
+
+async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+async fn docs() -> Html<String> {
+    Html(Redoc::new(SPEC_PATH).to_html())
+}
@@ -0,0 +1,69 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use serde::Serialize;
+use tracing::{error, instrument};
+
+use crate::{router::AppState, subscribe_form::Flash};
+
+/// Path of the hosted subscription form
+pub(crate) const FORM_PATH: &str = "/subscribe";
+
+pub fn router() -> Router<AppState> {
+    Router::new().route(FORM_PATH, get(subscribe_form))
+}
+
+#[derive(Serialize)]
+struct FormPage {
+    csrf_token: String,
+    form_token: String,
+    flash: Option<Flash>,
+}
+
+/// Renders the subscription form, along with the flash message left by the last submission
+#[instrument(name = "Render the subscription form", skip_all)]
+async fn subscribe_form(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let (csrf_token, csrf_cookie) = state.subscribe_form.csrf_token(&headers);
+    let flash = state.subscribe_form.flash(&headers);
+    let clear_flash = flash.is_some();
+    let page = FormPage {
+        csrf_token,
+        form_token: state.bot_protection.issue_form_token(),
+        flash,
+    };
+
+    let html = match state.templates.render("subscribe.html", page) {
+        Ok(html) => html,
+        Err(e) => {
+            error!(error.cause_chain = ?e, "Failed to render the subscription form");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let mut response = Html(html).into_response();
+    let headers = response.headers_mut();
+    // The page embeds per-visitor tokens
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    if let Some(cookie) = csrf_cookie {
+        headers.append(header::SET_COOKIE, cookie);
+    }
+    if clear_flash {
+        headers.append(
+            header::SET_COOKIE,
+            state.subscribe_form.clear_flash_cookie(),
+        );
+    }
+    response
+}
+
+/// Sends a visitor back to the form, showing them a flash message
+pub(crate) fn back_to_form(state: &AppState, flash: &Flash) -> Response {
+    let mut response = Redirect::to(FORM_PATH).into_response();
+    response
+        .headers_mut()
+        .append(header::SET_COOKIE, state.subscribe_form.flash_cookie(flash));
+    response
+}
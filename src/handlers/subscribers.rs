@@ -0,0 +1,128 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, instrument};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    error_code::ErrorCode,
+    extract::{Json, Query},
+    router::{AppState, DbPool, ErrorResponse},
+    utils::error_chain_fmt,
+};
+
+/// Number of subscribers returned when the query does not set a limit
+const DEFAULT_LIMIT: i64 = 100;
+
+/// Maximum number of subscribers returned by a single query
+const MAX_LIMIT: i64 = 1000;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/subscribers", get(list_subscribers))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Parameters {
+    /// Only list subscribers with this status, e.g. `confirmed`
+    status: Option<String>,
+    /// Maximum number of subscribers to return, at most 1000
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Subscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[derive(thiserror::Error)]
+pub enum SubscribersError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SubscribersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for SubscribersError {
+    #[instrument(skip_all)]
+    fn into_response(self) -> Response {
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = match self {
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
+        };
+
+        // Create the error response body
+        let body = ErrorResponse::new(status_code, code, self.to_string());
+
+        // Log the error
+        match self {
+            Self::UnexpectedError(e) => error!("{:?}", e),
+        }
+
+        body.into_response()
+    }
+}
+
+/// Lists the most recent subscribers, optionally filtered by status
+#[utoipa::path(
+    get,
+    path = "/subscribers",
+    tag = "subscribers",
+    params(Parameters),
+    security(("api_key" = ["subscribers:read"])),
+    responses(
+        (status = 200, body = [Subscriber]),
+        (status = 401, description = "Missing, unknown or revoked API key", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "The API key lacks the `subscribers:read` scope", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, body = ErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
+#[instrument(name = "List subscribers", skip_all)]
+pub async fn list_subscribers(
+    State(state): State<AppState>,
+    Query(parameters): Query<Parameters>,
+) -> Result<Json<Vec<Subscriber>>, SubscribersError> {
+    let subscribers = get_subscribers(&state.db, parameters)
+        .await
+        .context("Failed to fetch the subscribers.")?;
+    Ok(Json(subscribers))
+}
+
+#[instrument(name = "Get subscribers", skip_all)]
+async fn get_subscribers(
+    pool: &DbPool,
+    parameters: Parameters,
+) -> Result<Vec<Subscriber>, sqlx::Error> {
+    let limit = parameters
+        .limit
+        .unwrap_or(DEFAULT_LIMIT)
+        .clamp(1, MAX_LIMIT);
+    sqlx::query_as!(
+        Subscriber,
+        r#"SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE ($1::text IS NULL OR status = $1)
+        ORDER BY subscribed_at DESC
+        LIMIT $2"#,
+        parameters.status,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
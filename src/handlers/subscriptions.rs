@@ -1,47 +1,99 @@
 use crate::{
+    bot_protection::{Challenge, ChallengeError},
     domain::{NewSubscriber, SubscriberEmail, SubscriberName},
-    email_client::EmailClient,
-    router::{AppState, DbTransaction, ErrorResponse},
+    email_client::{EmailClient, EmailKind},
+    error_code::{ErrorCode, FieldError, ValidationErrors},
+    extract::{FormOrJson, Json},
+    handlers::subscribe_form::back_to_form,
+    rate_limit::RateLimited,
+    router::{AppState, DbPool, DbTransaction, ErrorResponse},
+    subscribe_form::{CsrfRejected, Flash},
+    suppression,
     utils::error_chain_fmt,
+    versioning::API_V1,
 };
 use anyhow::Context;
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
-    routing::post,
-    Form, Json, Router,
+    routing::{get, post},
+    Router,
 };
 use chrono::Utc;
 use rand::{distr::Alphanumeric, rng, Rng};
-use serde::Deserialize;
-use tracing::{error, instrument, warn};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, instrument, warn};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+/// Routes sending confirmation emails, throttled per client
 pub fn router() -> Router<AppState> {
     Router::new().route("/subscriptions", post(subscribe))
 }
 
-#[derive(Deserialize)]
-struct FormData {
+/// Routes the sign-up form calls before submitting
+pub fn form_router() -> Router<AppState> {
+    Router::new().route("/subscriptions/form_token", get(form_token))
+}
+
+/// A sign-up, sent as JSON or as a URL encoded form
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct FormData {
     email: String,
     name: String,
+    /// Hidden honeypot field, left empty by humans
+    #[serde(default)]
+    website: String,
+    form_token: Option<String>,
+    captcha_response: Option<String>,
+    /// Only sent by the hosted subscription form
+    csrf_token: Option<String>,
+}
+
+impl FormData {
+    fn challenge(&self) -> Challenge<'_> {
+        Challenge {
+            honeypot: &self.website,
+            form_token: self.form_token.as_deref(),
+            captcha_response: self.captcha_response.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct FormTokenResponse {
+    form_token: String,
 }
 
 impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
+    type Error = ValidationErrors;
 
-    fn try_from(value: FormData) -> Result<Self, String> {
-        let name = SubscriberName::parse(value.name)?;
-        let email = SubscriberEmail::parse(value.email)?;
-        Ok(NewSubscriber { email, name })
+    /// Validates every field, reporting all of the invalid ones at once
+    fn try_from(value: FormData) -> Result<Self, ValidationErrors> {
+        let email = SubscriberEmail::parse(value.email)
+            .map_err(|e| FieldError::new("email", ErrorCode::InvalidEmail, e));
+        let name = SubscriberName::parse(value.name)
+            .map_err(|e| FieldError::new("name", e.code(), e.to_string()));
+        match (email, name) {
+            (Ok(email), Ok(name)) => Ok(NewSubscriber { email, name }),
+            (email, name) => Err(ValidationErrors(
+                [email.err(), name.err()].into_iter().flatten().collect(),
+            )),
+        }
     }
 }
 
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
     #[error("{0}")]
-    ValidationError(String),
+    ValidationError(ValidationErrors),
+    #[error("{0}")]
+    ChallengeFailed(String),
+    #[error(transparent)]
+    CsrfRejected(#[from] CsrfRejected),
+    #[error(transparent)]
+    RateLimited(#[from] RateLimited),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -52,45 +104,153 @@ impl std::fmt::Debug for SubscribeError {
     }
 }
 
+impl From<ChallengeError> for SubscribeError {
+    fn from(e: ChallengeError) -> Self {
+        match e {
+            ChallengeError::Rejected(message) => Self::ChallengeFailed(message),
+            ChallengeError::UnexpectedError(e) => Self::UnexpectedError(e),
+        }
+    }
+}
+
+impl SubscribeError {
+    fn log(&self) {
+        match self {
+            Self::ValidationError(e) => warn!("{:?}", e),
+            Self::ChallengeFailed(e) => warn!("{:?}", e),
+            Self::CsrfRejected(e) => warn!("{:?}", e),
+            Self::RateLimited(e) => warn!("{:?}", e),
+            Self::UnexpectedError(e) => error!("{:?}", e),
+        }
+    }
+
+    /// Returns the message shown to visitors of the hosted form
+    fn flash(&self) -> Flash {
+        match self {
+            // Internal failures are only detailed in the logs
+            Self::UnexpectedError(_) => {
+                Flash::error("Something went wrong, please try again later.")
+            }
+            e => Flash::error(e.to_string()),
+        }
+    }
+}
+
 impl IntoResponse for SubscribeError {
     #[instrument(skip_all)]
     fn into_response(self) -> Response {
-        // Determine the appropriate status code.
-        let status_code = match self {
-            Self::ValidationError(_) => StatusCode::BAD_REQUEST,
-            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = match self {
+            Self::ValidationError(_) => (StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest),
+            Self::ChallengeFailed(_) => (StatusCode::BAD_REQUEST, ErrorCode::ChallengeFailed),
+            Self::CsrfRejected(_) => (StatusCode::FORBIDDEN, ErrorCode::CsrfRejected),
+            Self::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, ErrorCode::RateLimited),
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
         };
 
         // Create the error response body
-        let body = ErrorResponse::new(status_code.as_u16(), self.to_string());
+        let mut body = ErrorResponse::new(status_code, code, self.to_string());
+        if let Self::ValidationError(errors) = &self {
+            body = body.with_errors(errors.clone());
+        }
+        let mut response = body.into_response();
+        if let Self::RateLimited(e) = &self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, e.retry_after_header());
+        }
 
         // Log the error
-        match self {
-            Self::ValidationError(e) => warn!("{:?}", e),
-            Self::UnexpectedError(e) => error!("{:?}", e),
-        }
+        self.log();
 
-        (status_code, Json(body)).into_response()
+        response
     }
 }
 
+/// Adds a subscriber, sending visitors of the hosted form back to it with a flash message
+#[utoipa::path(
+    post,
+    path = "/subscriptions",
+    tag = "subscriptions",
+    request_body(content(
+        (FormData = "application/json"),
+        (FormData = "application/x-www-form-urlencoded"),
+    )),
+    responses(
+        (status = 200, description = "A confirmation email was sent"),
+        (status = 303, description = "Submission of the hosted form, redirected back to it"),
+        (status = 400, description = "Invalid or automated sign-up", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 415, description = "Unsupported body media type", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Missing or mistyped field", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "Too many sign-ups, see `Retry-After`", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, body = ErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
 #[instrument(name = "Add a new subscriber" , skip_all, fields(subscriber_email = data.email, subscriber_name = data.name))]
 pub async fn subscribe(
     State(state): State<AppState>,
-    Form(data): Form<FormData>,
-) -> Result<StatusCode, SubscribeError> {
-    let new_subscriber = data.try_into().map_err(SubscribeError::ValidationError)?;
+    headers: HeaderMap,
+    FormOrJson(mut data): FormOrJson<FormData>,
+) -> Response {
+    let Some(csrf_token) = data.csrf_token.take() else {
+        return add_subscriber(&state, data)
+            .await
+            .map(|()| StatusCode::OK)
+            .into_response();
+    };
+
+    let outcome = match state
+        .subscribe_form
+        .verify_csrf_token(&headers, &csrf_token)
+    {
+        Ok(()) => add_subscriber(&state, data).await,
+        Err(e) => Err(e.into()),
+    };
+    let flash = match outcome {
+        Ok(()) => Flash::success("Thanks for subscribing! Check your inbox to confirm your email."),
+        Err(e) => {
+            e.log();
+            e.flash()
+        }
+    };
+    back_to_form(&state, &flash)
+}
+
+async fn add_subscriber(state: &AppState, data: FormData) -> Result<(), SubscribeError> {
+    state
+        .bot_protection
+        .verify(&state.db, &data.challenge())
+        .await?;
+    let new_subscriber: NewSubscriber = data.try_into().map_err(SubscribeError::ValidationError)?;
+    state
+        .rate_limiter
+        .check_email(new_subscriber.email.normalized())?;
+    state
+        .email_domains
+        .verify(&new_subscriber.email)
+        .await
+        .map_err(|e| {
+            SubscribeError::ValidationError(
+                FieldError::new("email", e.code(), e.to_string()).into(),
+            )
+        })?;
 
     let mut transaction = state
         .db
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool.")?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let subscriber = insert_subscriber(&mut transaction, &new_subscriber)
         .await
         .context("Failed to insert new subscriber in the database.")?;
+    if subscriber.status != "pending_confirmation" {
+        // Answer as for a new sign-up, so that the response doesn't tell who is subscribed
+        info!("The address is subscribed already, no confirmation email is sent");
+        return Ok(());
+    }
+    // A pending subscriber signing up again gets a new link, the previous one may have expired
     let subscription_token = generate_subscription_token();
-    store_token(&mut transaction, subscriber_id, &subscription_token)
+    store_token(&mut transaction, subscriber.id, &subscription_token)
         .await
         .context("Failed to store the confirmation token for a new subscriber.")?;
     transaction
@@ -98,6 +258,7 @@ pub async fn subscribe(
         .await
         .context("Failed to commit SQL transaction to store a new subscriber.")?;
     send_confirmation_email(
+        &state.db,
         &state.email_client,
         new_subscriber,
         &state.base_url,
@@ -105,37 +266,72 @@ pub async fn subscribe(
     )
     .await
     .context("Failed to send a confirmation email.")?;
-    Ok(StatusCode::OK)
+    Ok(())
+}
+
+/// Issues a signed timestamp to embed in the sign-up form
+#[utoipa::path(
+    get,
+    path = "/subscriptions/form_token",
+    tag = "subscriptions",
+    responses((status = 200, body = FormTokenResponse)),
+)]
+#[instrument(name = "Issue a sign-up form token", skip_all)]
+pub(crate) async fn form_token(State(state): State<AppState>) -> Json<FormTokenResponse> {
+    Json(FormTokenResponse {
+        form_token: state.bot_protection.issue_form_token(),
+    })
 }
 
+/// A subscriber as saved in the database
+struct StoredSubscriber {
+    id: Uuid,
+    status: String,
+}
+
+/// Saves a new subscriber, or returns the one saved already under the same address
 #[instrument(name = "Save new subscriber details in the database", skip_all)]
 async fn insert_subscriber(
     transaction: &mut DbTransaction<'_>,
     new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
-    let subscriber_id = Uuid::new_v4();
-    sqlx::query!(
-        r#"INSERT INTO subscriptions (id, email, name, subscribed_at, status) VALUES ($1, $2, $3, $4, 'pending_confirmation')"#,
-        subscriber_id,
+) -> Result<StoredSubscriber, sqlx::Error> {
+    // The no-op update makes the existing row come back on conflict
+    sqlx::query_as!(
+        StoredSubscriber,
+        r#"INSERT INTO subscriptions (id, email, email_normalized, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, $5, 'pending_confirmation')
+        ON CONFLICT (email_normalized) DO UPDATE SET email_normalized = EXCLUDED.email_normalized
+        RETURNING id, status"#,
+        Uuid::new_v4(),
         new_subscriber.email.as_ref(),
+        new_subscriber.email.normalized(),
         new_subscriber.name.as_ref(),
         Utc::now()
     )
-    .execute(&mut **transaction)
-    .await?;
-    Ok(subscriber_id)
+    .fetch_one(&mut **transaction)
+    .await
 }
 
 #[instrument(name = "Send a confirmation email to a new subscriber", skip_all)]
 async fn send_confirmation_email(
+    pool: &DbPool,
     email_client: &EmailClient,
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
+    // Never mail a suppressed address, even if it subscribes again
+    if suppression::is_suppressed(pool, new_subscriber.email.as_ref())
+        .await
+        .context("Failed to check the suppression list.")?
+    {
+        info!("Skipping the confirmation email to a suppressed address");
+        return Ok(());
+    }
+
     let confirmation_link = format!(
-        "{}/subscriptions/confirm?subscription_token={}",
-        base_url, subscription_token
+        "{}{}/subscriptions/confirm?subscription_token={}",
+        base_url, API_V1, subscription_token
     );
     let html_content = format!(
         "Welcome to our newsletter! We're glad to have you.<br />\
@@ -149,6 +345,7 @@ async fn send_confirmation_email(
     );
     email_client
         .send_email(
+            EmailKind::Confirmation,
             &new_subscriber.email,
             "Welcome!",
             &html_content,
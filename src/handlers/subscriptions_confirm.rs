@@ -1,16 +1,20 @@
 use anyhow::Context;
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
     routing::get,
-    Json, Router,
+    Router,
 };
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use tracing::{error, instrument, warn};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 use crate::{
+    error_code::ErrorCode,
+    extract::{Json, Query},
     router::{AppState, DbPool, ErrorResponse},
     utils::error_chain_fmt,
 };
@@ -19,15 +23,32 @@ pub fn router() -> Router<AppState> {
     Router::new().route("/subscriptions/confirm", get(confirm))
 }
 
-#[derive(Deserialize)]
-struct Parameters {
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct Parameters {
+    /// Token of the confirmation link
     subscription_token: String,
 }
 
+/// Outcome of following a valid confirmation link
+#[derive(Serialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Confirmation {
+    Confirmed,
+    AlreadyConfirmed,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ConfirmationResponse {
+    status: Confirmation,
+}
+
 #[derive(thiserror::Error)]
 pub enum ConfirmationError {
     #[error("There is no subscriber associated with the provided token.")]
     UnknownToken,
+    #[error("The confirmation link has expired.")]
+    ExpiredToken,
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -38,63 +59,198 @@ impl std::fmt::Debug for ConfirmationError {
     }
 }
 
+impl ConfirmationError {
+    /// Returns the status code and error code of the error
+    fn status_and_code(&self) -> (StatusCode, ErrorCode) {
+        match self {
+            Self::UnknownToken => (StatusCode::UNAUTHORIZED, ErrorCode::UnknownToken),
+            Self::ExpiredToken => (StatusCode::GONE, ErrorCode::ExpiredToken),
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
+        }
+    }
+
+    fn log(&self) {
+        match self {
+            Self::UnknownToken | Self::ExpiredToken => warn!("{:?}", self),
+            Self::UnexpectedError(e) => error!("{:?}", e),
+        }
+    }
+}
+
 impl IntoResponse for ConfirmationError {
     fn into_response(self) -> Response {
-        // Determine the appropriate status code.
-        let status_code = match self {
-            Self::UnknownToken => StatusCode::UNAUTHORIZED,
-            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-        };
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = self.status_and_code();
 
         // Create the error response body.
-        let body = ErrorResponse::new(status_code.as_u16(), self.to_string());
+        let body = ErrorResponse::new(status_code, code, self.to_string());
 
         // Log the error
-        match self {
-            Self::UnknownToken => warn!("{:?}", self),
-            Self::UnexpectedError(e) => error!("{:?}", e),
-        }
+        self.log();
 
-        (status_code, Json(body)).into_response()
+        body.into_response()
     }
 }
 
+/// Confirms a subscriber, answering browsers with a landing page and API clients with JSON
+#[utoipa::path(
+    get,
+    path = "/subscriptions/confirm",
+    tag = "subscriptions",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The subscriber is confirmed", content(
+            (ConfirmationResponse = "application/json"),
+            ("text/html"),
+        )),
+        (status = 303, description = "Browser redirected to the configured landing page"),
+        (status = 400, description = "Missing token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Unknown token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 410, description = "Expired token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, body = ErrorResponse, content_type = "application/problem+json"),
+    ),
+)]
 #[instrument(name = "Confirm a pending subscriber", skip_all)]
 pub async fn confirm(
     State(state): State<AppState>,
-    parameters: Query<Parameters>,
-) -> Result<StatusCode, ConfirmationError> {
-    let subscriber_id = get_subscriber_id_from_token(&state.db, &parameters.subscription_token)
+    headers: HeaderMap,
+    Query(parameters): Query<Parameters>,
+) -> Response {
+    let outcome = confirm_token(&state, &parameters.subscription_token).await;
+    if accepts_html(&headers) {
+        return landing_page(&state, outcome);
+    }
+    match outcome {
+        Ok(status) => Json(ConfirmationResponse { status }).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn confirm_token(
+    state: &AppState,
+    subscription_token: &str,
+) -> Result<Confirmation, ConfirmationError> {
+    let token = get_token(&state.db, subscription_token)
         .await
         .context("Failed to retrieve the subscriber id associated with the provided token.")?
         .ok_or(ConfirmationError::UnknownToken)?;
-    confirm_subscriber(&state.db, subscriber_id)
+    if token.status != "pending_confirmation" {
+        return already_handled(&token.status);
+    }
+    let age = (Utc::now() - token.created_at).to_std().unwrap_or_default();
+    if age > state.confirmation.token_ttl() {
+        return Err(ConfirmationError::ExpiredToken);
+    }
+    let confirmed = confirm_subscriber(&state.db, token.subscriber_id)
         .await
         .context("Failed to update the subscriber status to `confirmed`.")?;
-    Ok(StatusCode::OK)
+    if !confirmed {
+        // The status changed since the token was read, e.g. the link was followed twice
+        let status = get_status(&state.db, token.subscriber_id)
+            .await
+            .context("Failed to retrieve the subscriber status.")?;
+        return already_handled(&status);
+    }
+    Ok(Confirmation::Confirmed)
+}
+
+/// Answers a link followed once its subscriber is no longer pending
+///
+/// Bounced or complained subscribers stay inactive, their links are void.
+fn already_handled(status: &str) -> Result<Confirmation, ConfirmationError> {
+    match status {
+        "confirmed" => Ok(Confirmation::AlreadyConfirmed),
+        _ => Err(ConfirmationError::UnknownToken),
+    }
+}
+
+/// Tells whether the client asked for an HTML page, as browsers do
+fn accepts_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// Redirects to the page configured for the outcome, or renders the built-in one
+fn landing_page(state: &AppState, outcome: Result<Confirmation, ConfirmationError>) -> Response {
+    let redirects = &state.confirmation.redirects;
+    let (status_code, page, redirect) = match &outcome {
+        Ok(Confirmation::Confirmed) => (StatusCode::OK, "confirmed", &redirects.confirmed),
+        Ok(Confirmation::AlreadyConfirmed) => (
+            StatusCode::OK,
+            "already_confirmed",
+            &redirects.already_confirmed,
+        ),
+        Err(e) => {
+            e.log();
+            let (page, redirect) = match e {
+                ConfirmationError::UnknownToken => ("unknown_token", &redirects.unknown_token),
+                ConfirmationError::ExpiredToken => ("expired", &redirects.expired),
+                ConfirmationError::UnexpectedError(_) => ("error", &None),
+            };
+            (e.status_and_code().0, page, redirect)
+        }
+    };
+
+    if let Some(url) = redirect {
+        return Redirect::to(url).into_response();
+    }
+    match state
+        .templates
+        .render("confirmation.html", minijinja::context! { outcome => page })
+    {
+        Ok(html) => (status_code, Html(html)).into_response(),
+        Err(e) => {
+            error!(error.cause_chain = ?e, "Failed to render the confirmation page");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
 }
 
+/// Marks a pending subscriber as confirmed
+///
+/// # Returns
+/// true if the subscriber was still pending
 #[instrument(name = "Mark subscriber as confirmed", skip_all)]
-async fn confirm_subscriber(pool: &DbPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+async fn confirm_subscriber(pool: &DbPool, subscriber_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'confirmed'
+        WHERE id = $1 AND status = 'pending_confirmation'"#,
         subscriber_id
     )
     .execute(pool)
     .await?;
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
-#[instrument(name = "Get subscriber_id from subscription_token", skip_all)]
-async fn get_subscriber_id_from_token(
-    pool: &DbPool,
-    subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1"#,
+#[instrument(name = "Get the subscriber status", skip_all)]
+async fn get_status(pool: &DbPool, subscriber_id: Uuid) -> Result<String, sqlx::Error> {
+    let record = sqlx::query!(
+        r#"SELECT status FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(record.status)
+}
+
+/// A confirmation token and the subscriber it belongs to
+struct Token {
+    subscriber_id: Uuid,
+    status: String,
+    created_at: DateTime<Utc>,
+}
+
+#[instrument(name = "Get subscriber from subscription_token", skip_all)]
+async fn get_token(pool: &DbPool, subscription_token: &str) -> Result<Option<Token>, sqlx::Error> {
+    sqlx::query_as!(
+        Token,
+        r#"SELECT t.subscriber_id, s.status, t.created_at
+        FROM subscription_tokens t JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE t.subscription_token = $1"#,
         subscription_token
     )
     .fetch_optional(pool)
-    .await?;
-    Ok(result.map(|r| r.subscriber_id))
+    .await
 }
@@ -0,0 +1,174 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use serde::Deserialize;
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    authentication::basic_authentication,
+    domain::SubscriberEmail,
+    error_code::ErrorCode,
+    extract::{ExtractionRejection, Json},
+    router::{AppState, DbPool, ErrorResponse},
+    suppression,
+    utils::error_chain_fmt,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/webhooks/postmark", post(postmark_webhook))
+}
+
+/// A webhook record posted by Postmark, discriminated by its `RecordType`.
+#[derive(Deserialize)]
+#[serde(tag = "RecordType")]
+pub enum PostmarkEvent {
+    Bounce(BounceRecord),
+    SpamComplaint(SpamComplaintRecord),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BounceRecord {
+    email: String,
+    #[serde(rename = "Type")]
+    bounce_type: String,
+    #[serde(default)]
+    inactive: bool,
+}
+
+impl BounceRecord {
+    /// Whether the bounce means the address should never be mailed again.
+    fn is_permanent(&self) -> bool {
+        self.inactive || matches!(self.bounce_type.as_str(), "HardBounce" | "BadEmailAddress")
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SpamComplaintRecord {
+    email: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error("Authentication failed.")]
+    AuthError(#[source] anyhow::Error),
+    #[error("{0}")]
+    InvalidPayload(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for WebhookError {
+    #[instrument(skip_all)]
+    fn into_response(self) -> Response {
+        // Determine the appropriate status code and error code.
+        let (status_code, code) = match self {
+            Self::AuthError(_) => (StatusCode::UNAUTHORIZED, ErrorCode::Unauthorized),
+            Self::InvalidPayload(_) => (StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest),
+            Self::UnexpectedError(_) => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
+        };
+
+        // Create the error response body
+        let body = ErrorResponse::new(status_code, code, self.to_string());
+
+        // Log the error
+        match &self {
+            Self::AuthError(_) | Self::InvalidPayload(_) => warn!("{:?}", self),
+            Self::UnexpectedError(e) => error!("{:?}", e),
+        }
+
+        let mut response = body.into_response();
+        if let Self::AuthError(_) = self {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(r#"Basic realm="webhooks""#),
+            );
+        }
+        response
+    }
+}
+
+#[instrument(name = "Handle a Postmark webhook", skip_all)]
+pub async fn postmark_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    payload: Result<Json<PostmarkEvent>, ExtractionRejection>,
+) -> Result<StatusCode, WebhookError> {
+    // Authenticate before looking at the payload
+    let webhooks = state
+        .webhooks
+        .as_deref()
+        .context("The webhooks are disabled, no credentials are configured.")
+        .map_err(WebhookError::AuthError)?;
+    basic_authentication(&headers)
+        .and_then(|credentials| credentials.validate(webhooks))
+        .map_err(WebhookError::AuthError)?;
+    let Json(event) = payload.map_err(|e| WebhookError::InvalidPayload(e.to_string()))?;
+
+    match event {
+        PostmarkEvent::Bounce(record) if record.is_permanent() => {
+            info!(
+                bounce_type = record.bounce_type,
+                "Received a permanent bounce"
+            );
+            update_subscriber_status(&state.db, &record.email, "bounced")
+                .await
+                .context("Failed to mark the subscriber as bounced.")?;
+            suppression::suppress(&state.db, &record.email, "hard_bounce", "postmark")
+                .await
+                .context("Failed to suppress a bounced address.")?;
+        }
+        PostmarkEvent::Bounce(record) => {
+            info!(
+                bounce_type = record.bounce_type,
+                "Ignoring a transient bounce"
+            );
+        }
+        PostmarkEvent::SpamComplaint(record) => {
+            info!("Received a spam complaint");
+            update_subscriber_status(&state.db, &record.email, "complained")
+                .await
+                .context("Failed to mark the subscriber as complained.")?;
+            suppression::suppress(&state.db, &record.email, "spam_complaint", "postmark")
+                .await
+                .context("Failed to suppress a complaining address.")?;
+        }
+        PostmarkEvent::Other => {
+            info!("Ignoring an unsupported webhook record type");
+        }
+    }
+    Ok(StatusCode::OK)
+}
+
+/// Marks a subscriber as undeliverable.
+///
+/// A complaint is never downgraded to a bounce.
+#[instrument(name = "Update subscriber delivery status", skip(pool, email))]
+async fn update_subscriber_status(
+    pool: &DbPool,
+    email: &str,
+    status: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = $1 WHERE email_normalized = $2 AND status <> 'complained'"#,
+        status,
+        SubscriberEmail::normalize(email)
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
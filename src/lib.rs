@@ -1,13 +1,26 @@
 mod handlers;
 
+pub mod api_keys;
+pub mod authentication;
+pub mod bot_protection;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
+pub mod email_domains;
+pub mod error_code;
+pub mod extract;
 pub mod middleware;
+pub mod openapi;
+pub mod rate_limit;
 pub mod router;
 pub mod server;
+pub mod subscribe_form;
+pub mod suppression;
 pub mod telemetry;
+pub mod templates;
+pub mod users;
 pub mod utils;
+pub mod versioning;
 
 pub use configuration::Settings;
 pub use server::HttpServer;
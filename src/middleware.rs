@@ -1,13 +1,17 @@
-use axum::http::HeaderName;
-use std::sync::Arc;
+use anyhow::Context;
+use axum::http::{HeaderName, HeaderValue, Method};
+use std::{sync::Arc, time::Duration};
 use tower_http::{
     classify::{ServerErrorsAsFailures, SharedClassifier},
+    cors::{AllowOrigin, CorsLayer},
     request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
     sensitive_headers::{SetSensitiveRequestHeadersLayer, SetSensitiveResponseHeadersLayer},
     trace::{DefaultMakeSpan, TraceLayer},
 };
 use tracing::Level;
 
+use crate::configuration::CorsSettings;
+
 /// Returns a `TraceLayer` for HTTP requests and responses.
 /// The `TraceLayer` is used to trace requests and responses in the application.
 pub fn trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>> {
@@ -37,3 +41,47 @@ pub fn set_x_request_id() -> SetRequestIdLayer<MakeRequestUuid> {
 pub fn propagate_x_request_id() -> PropagateRequestIdLayer {
     PropagateRequestIdLayer::new(HeaderName::from_static("x-request-id"))
 }
+
+/// Returns a `CorsLayer` answering preflight requests from the configured origins
+///
+/// # Arguments
+/// * `conf` - CORS settings
+///
+/// # Returns
+/// The layer if successful, Error if an origin, method or header is invalid
+pub fn cors_layer(conf: &CorsSettings) -> anyhow::Result<CorsLayer> {
+    let allow_origin = if conf.allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = conf
+            .allowed_origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin).with_context(|| format!("Invalid origin {}", origin))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+    let methods = conf
+        .allowed_methods
+        .iter()
+        .map(|method| {
+            Method::from_bytes(method.as_bytes())
+                .with_context(|| format!("Invalid method {}", method))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let headers = conf
+        .allowed_headers
+        .iter()
+        .map(|header| {
+            HeaderName::from_bytes(header.as_bytes())
+                .with_context(|| format!("Invalid header {}", header))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .max_age(Duration::from_secs(conf.max_age_secs)))
+}
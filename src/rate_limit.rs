@@ -0,0 +1,203 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use governor::{clock::Clock, DefaultKeyedRateLimiter, Quota, RateLimiter};
+use std::{
+    hash::Hash,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+use tracing::warn;
+
+use crate::{
+    configuration::RateLimitSettings,
+    error_code::ErrorCode,
+    router::{AppState, ErrorResponse},
+};
+
+/// Number of tracked keys above which stale entries are evicted
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// Error returned when a caller exceeds its quota
+#[derive(thiserror::Error, Debug)]
+#[error("Too many requests. Retry in {} seconds.", self.retry_after_secs())]
+pub struct RateLimited {
+    retry_after: Duration,
+}
+
+impl RateLimited {
+    /// Seconds the caller should wait before retrying, rounded up
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+
+    /// Value of the `Retry-After` header for this error
+    pub fn retry_after_header(&self) -> HeaderValue {
+        HeaderValue::from(self.retry_after_secs())
+    }
+}
+
+impl IntoResponse for RateLimited {
+    fn into_response(self) -> Response {
+        let status_code = StatusCode::TOO_MANY_REQUESTS;
+
+        // Create the error response body
+        let body = ErrorResponse::new(status_code, ErrorCode::RateLimited, self.to_string());
+
+        // Log the error
+        warn!("{:?}", self);
+
+        let mut response = body.into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, self.retry_after_header());
+        response
+    }
+}
+
+/// Rate limiter for the public subscription endpoint, keyed by client IP and target email
+pub struct SubscriptionRateLimiter {
+    by_ip: DefaultKeyedRateLimiter<IpAddr>,
+    by_email: DefaultKeyedRateLimiter<String>,
+    trusted_proxy_hops: usize,
+}
+
+impl SubscriptionRateLimiter {
+    /// Creates a new rate limiter from configuration settings
+    pub fn new(conf: &RateLimitSettings) -> Self {
+        Self {
+            by_ip: RateLimiter::keyed(Quota::per_minute(conf.subscriptions_per_ip_per_minute)),
+            by_email: RateLimiter::keyed(Quota::per_hour(conf.subscriptions_per_email_per_hour)),
+            trusted_proxy_hops: conf.trusted_proxy_hops,
+        }
+    }
+
+    /// Records a request from a client IP
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), RateLimited> {
+        check(&self.by_ip, ip)
+    }
+
+    /// Records a request targeting an email address
+    pub fn check_email(&self, email: &str) -> Result<(), RateLimited> {
+        check(&self.by_email, email.to_lowercase())
+    }
+
+    /// Determines the client IP of a request
+    ///
+    /// With `n` trusted proxy hops, the client is the `n`-th `X-Forwarded-For` entry counted
+    /// from the right, since every trusted proxy appends the address it received the request
+    /// from. Falls back to the peer address if the header has fewer entries than that.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: IpAddr) -> IpAddr {
+        if self.trusted_proxy_hops == 0 {
+            return peer;
+        }
+
+        let forwarded: Vec<IpAddr> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|ip| ip.trim().parse().ok())
+            .collect();
+
+        forwarded
+            .len()
+            .checked_sub(self.trusted_proxy_hops)
+            .map(|i| forwarded[i])
+            .unwrap_or(peer)
+    }
+}
+
+fn check<K: Hash + Eq + Clone>(
+    limiter: &DefaultKeyedRateLimiter<K>,
+    key: K,
+) -> Result<(), RateLimited> {
+    if limiter.len() > MAX_TRACKED_KEYS {
+        limiter.retain_recent();
+    }
+    limiter.check_key(&key).map_err(|not_until| RateLimited {
+        retry_after: not_until.wait_time_from(limiter.clock().now()),
+    })
+}
+
+/// Middleware limiting the number of requests per client IP
+pub(crate) async fn limit_subscriptions_by_ip(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, RateLimited> {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    let client_ip = state.rate_limiter.client_ip(request.headers(), peer);
+    state.rate_limiter.check_ip(client_ip)?;
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok};
+    use std::num::NonZeroU32;
+
+    fn rate_limiter(trusted_proxy_hops: usize) -> SubscriptionRateLimiter {
+        SubscriptionRateLimiter::new(&RateLimitSettings {
+            trusted_proxy_hops,
+            subscriptions_per_ip_per_minute: NonZeroU32::new(2).unwrap(),
+            subscriptions_per_email_per_hour: NonZeroU32::new(1).unwrap(),
+        })
+    }
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_without_trusted_proxies() {
+        let limiter = rate_limiter(0);
+        let headers = forwarded_for("1.1.1.1");
+        assert_eq!(limiter.client_ip(&headers, ip("10.0.0.1")), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn client_ip_is_taken_from_the_trusted_hop() {
+        let limiter = rate_limiter(2);
+        let headers = forwarded_for("6.6.6.6, 1.1.1.1, 10.0.0.2");
+        assert_eq!(limiter.client_ip(&headers, ip("10.0.0.1")), ip("1.1.1.1"));
+    }
+
+    #[test]
+    fn peer_address_is_used_when_forwarded_for_is_too_short() {
+        let limiter = rate_limiter(2);
+        let headers = forwarded_for("1.1.1.1");
+        assert_eq!(limiter.client_ip(&headers, ip("10.0.0.1")), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn requests_over_the_ip_quota_are_rejected() {
+        let limiter = rate_limiter(0);
+        assert_ok!(limiter.check_ip(ip("1.1.1.1")));
+        assert_ok!(limiter.check_ip(ip("1.1.1.1")));
+        let error = limiter.check_ip(ip("1.1.1.1")).unwrap_err();
+        assert!(error.retry_after_secs() >= 1);
+        assert_ok!(limiter.check_ip(ip("2.2.2.2")));
+    }
+
+    #[test]
+    fn email_quota_ignores_case() {
+        let limiter = rate_limiter(0);
+        assert_ok!(limiter.check_email("ursula@example.com"));
+        assert_err!(limiter.check_email("Ursula@Example.com"));
+    }
+}
@@ -1,11 +1,31 @@
-use axum::{http::header, Router};
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
 use std::sync::Arc;
 use tower::ServiceBuilder;
+use tower_http::cors::CorsLayer;
 
 use crate::{
+    api_keys::Scope,
+    authentication,
+    bot_protection::BotProtection,
+    configuration::{
+        ApiDocsSettings, BasicAuthSettings, ConfirmationSettings, LegacyRoutesSettings,
+    },
     email_client::EmailClient,
-    handlers::{health_check, newsletters, subscriptions, subscriptions_confirm},
+    email_domains::EmailDomainCheck,
+    error_code::{ErrorCode, FieldError, ValidationErrors},
+    handlers::{
+        admin, health_check, newsletters, openapi, subscribe_form, subscribers, subscriptions,
+        subscriptions_confirm, webhooks,
+    },
     middleware,
+    rate_limit::{self, SubscriptionRateLimiter},
+    subscribe_form::SubscribeForm,
+    templates::Templates,
+    versioning::{self, LegacyRoutes, API_V1},
 };
 
 /// Postgres database pool type
@@ -14,17 +34,52 @@ pub type DbPool = sqlx::PgPool;
 /// Postgres database transaction type
 pub type DbTransaction<'a> = sqlx::Transaction<'a, sqlx::Postgres>;
 
-/// Error response body structure
-#[derive(serde::Serialize)]
+/// Media type of error responses, RFC 7807
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Error response body, an RFC 7807 problem document extended with an error code
+#[derive(serde::Serialize, utoipa::ToSchema)]
 pub struct ErrorResponse {
-    code: u16,
-    message: String,
+    /// Problems are only told apart by their code, not by a dedicated type URI
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    code: ErrorCode,
+    /// Every field that failed validation
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
 }
 
 impl ErrorResponse {
     /// Creates a new error response body
-    pub fn new(code: u16, message: String) -> Self {
-        Self { code, message }
+    pub fn new(status: StatusCode, code: ErrorCode, detail: String) -> Self {
+        Self {
+            problem_type: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            detail,
+            code,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Adds the fields that failed validation
+    pub fn with_errors(mut self, errors: ValidationErrors) -> Self {
+        self.errors = errors.0;
+        self
+    }
+}
+
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(self)).into_response();
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        response
     }
 }
 
@@ -35,16 +90,32 @@ pub(crate) struct AppState {
     pub db: DbPool,
     pub email_client: Arc<EmailClient>,
     pub base_url: Arc<String>,
+    pub webhooks: Option<Arc<BasicAuthSettings>>,
+    pub admin: Option<Arc<BasicAuthSettings>>,
+    pub rate_limiter: Arc<SubscriptionRateLimiter>,
+    pub bot_protection: Arc<BotProtection>,
+    pub email_domains: Arc<EmailDomainCheck>,
+    pub confirmation: Arc<ConfirmationSettings>,
+    pub templates: Arc<Templates>,
+    pub subscribe_form: Arc<SubscribeForm>,
 }
 
 /// Builds the API router with all routes and middlewares
 ///
 /// # Arguments
 /// * `app_state` - Application state
+/// * `cors` - CORS policy of the public routes
+/// * `api_docs` - API documentation settings
+/// * `legacy_routes` - Deprecation of the unversioned paths
 ///
 /// # Returns
 /// A configured router with all routes and middleware
-pub(crate) fn build_router(app_state: AppState) -> Router {
+pub(crate) fn build_router(
+    app_state: AppState,
+    cors: CorsLayer,
+    api_docs: &ApiDocsSettings,
+    legacy_routes: &LegacyRoutesSettings,
+) -> Router {
     // Configure headers that should be treated as sensitive in logs
     let sensitive_headers = Arc::new([
         header::AUTHORIZATION,
@@ -63,12 +134,80 @@ pub(crate) fn build_router(app_state: AppState) -> Router {
         .layer(middleware::sensitive_response_headers(sensitive_headers))
         .layer(middleware::propagate_x_request_id());
 
+    // Pages and documents served outside of the versioned API
+    let site_router = Router::new()
+        .merge(health_check::router())
+        .merge(subscribe_form::router())
+        .merge(openapi::router(api_docs.ui))
+        .layer(cors.clone());
+
+    let api_v1_router = api_v1(&app_state, cors);
+
+    // The unversioned paths predate `/api/v1` and are kept as its deprecated aliases
+    let legacy_router = api_v1_router
+        .clone()
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(LegacyRoutes::new(legacy_routes, API_V1)),
+            versioning::deprecate_legacy_route,
+        ));
+
     // Create router with all routes and middleware
     Router::new()
-        .merge(health_check::router())
-        .merge(subscriptions::router())
-        .merge(subscriptions_confirm::router())
-        .merge(newsletters::router())
+        .merge(site_router)
+        .nest(API_V1, api_v1_router)
+        .merge(legacy_router)
         .layer(middleware)
         .with_state(app_state)
 }
+
+/// Builds the routes of version 1 of the API
+///
+/// A new version gets its own function and is nested alongside `/api/v1` in
+/// [`build_router`], reusing the handlers whose contract doesn't change.
+///
+/// # Arguments
+/// * `app_state` - Application state
+/// * `cors` - CORS policy of the public routes
+fn api_v1(app_state: &AppState, cors: CorsLayer) -> Router<AppState> {
+    // Admin routes are only reachable with admin credentials
+    let admin_router = admin::router().route_layer(axum::middleware::from_fn_with_state(
+        app_state.clone(),
+        authentication::require_admin,
+    ));
+
+    // Public subscriptions trigger outbound emails, so they are throttled per client.
+    // Fetching a form token doesn't, and must not use up the quota of the sign-up it
+    // precedes.
+    let subscriptions_router =
+        subscriptions::router().route_layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            rate_limit::limit_subscriptions_by_ip,
+        ));
+
+    // Only the routes meant for browsers on other origins, such as our marketing site,
+    // answer cross-origin requests
+    let public_router = Router::new()
+        .merge(subscriptions_router)
+        .merge(subscriptions::form_router())
+        .merge(subscriptions_confirm::router())
+        .layer(cors);
+
+    // Machine clients authenticate with API keys granted the scope of the route
+    let require_scope = |scope| {
+        axum::middleware::from_fn_with_state(
+            (app_state.clone(), scope),
+            authentication::require_scope,
+        )
+    };
+    let newsletters_router =
+        newsletters::router().route_layer(require_scope(Scope::NewslettersPublish));
+    let subscribers_router =
+        subscribers::router().route_layer(require_scope(Scope::SubscribersRead));
+
+    Router::new()
+        .merge(public_router)
+        .merge(newsletters_router)
+        .merge(subscribers_router)
+        .merge(webhooks::router())
+        .merge(admin_router)
+}
@@ -1,12 +1,18 @@
 use anyhow::Context;
 use axum::Router;
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 use tokio::net::TcpListener;
 
 use crate::{
+    bot_protection::BotProtection,
     configuration::Settings,
-    email_client::EmailClient,
+    email_client::{DeliveryLog, DkimSigner, EmailClient, SendThrottle, SmtpRelay},
+    email_domains::EmailDomainCheck,
+    middleware,
+    rate_limit::SubscriptionRateLimiter,
     router::{build_router, AppState},
+    subscribe_form::SubscribeForm,
+    templates::Templates,
 };
 
 /// HTTP Server wrapper to facilitate integration testing and service initialization
@@ -36,10 +42,14 @@ impl HttpServer {
             .port();
 
         // Builds the application state from configuration settings
-        let app_state = build_app_state(conf);
+        let app_state = build_app_state(conf)?;
+
+        // Build the CORS policy of the public routes
+        let cors =
+            middleware::cors_layer(&conf.server.cors).context("Invalid server.cors settings")?;
 
         // Build router with app state
-        let service = build_router(app_state);
+        let service = build_router(app_state, cors, &conf.api_docs, &conf.server.legacy_routes);
 
         Ok(Self {
             listener,
@@ -53,7 +63,11 @@ impl HttpServer {
     /// # Returns
     /// Ok(()) if successful, Error otherwise
     pub async fn run(self) -> anyhow::Result<()> {
-        axum::serve(self.listener, self.service)
+        // Expose the peer address to the rate limiter
+        let service = self
+            .service
+            .into_make_service_with_connect_info::<SocketAddr>();
+        axum::serve(self.listener, service)
             .await
             .context("Failed to start server")
     }
@@ -64,33 +78,97 @@ impl HttpServer {
     }
 }
 
-fn build_app_state(conf: &Settings) -> AppState {
+fn build_app_state(conf: &Settings) -> anyhow::Result<AppState> {
     // Create database connection pool from configuration
     let db = conf.database.get_connection_pool();
 
-    // Parse sender email from configuration
-    let sender_email = conf
+    // Parse sender and reply-to mailboxes from configuration
+    let sender = conf
         .email_client
         .sender()
-        .expect("Invalid sender email address");
+        .map_err(anyhow::Error::msg)
+        .context("Invalid email_client.sender_email setting")?;
+    let reply_to = conf
+        .email_client
+        .reply_to()
+        .map_err(anyhow::Error::msg)
+        .context("Invalid email_client.reply_to setting")?;
 
     // Create new email client with configuration parameters
-    let email_client = EmailClient::new(
+    let mut email_client = EmailClient::new(
         &conf.email_client.base_url,
-        sender_email,
+        sender.clone(),
         conf.email_client.authorization_token.clone(),
         conf.email_client.timeout(),
     );
+    if let Some(reply_to) = reply_to {
+        email_client = email_client.with_reply_to(reply_to);
+    }
+    if let Some(smtp) = &conf.email_client.smtp {
+        let relay = SmtpRelay::new(smtp, conf.email_client.timeout())
+            .context("Invalid email_client.smtp settings")?;
+        email_client = email_client.with_smtp_relay(relay);
+    }
+    if let Some(dkim) = &conf.email_client.dkim {
+        let signer = DkimSigner::new(dkim).context("Invalid email_client.dkim settings")?;
+        email_client = email_client.with_dkim_signer(signer);
+        email_client
+            .check_sender(&sender)
+            .map_err(anyhow::Error::msg)
+            .context("Invalid email_client.dkim settings")?;
+    }
+    let email_client = email_client
+        .with_throttle(SendThrottle::new(
+            conf.email_client.max_messages_per_second,
+            conf.email_client.max_concurrent_requests,
+        ))
+        .with_delivery_log(DeliveryLog::new(db.clone()));
     // Wrap email client in Arc for thread-safe sharing
     let email_client = Arc::new(email_client);
 
     // Get base URL from configuration
     let base_url = Arc::new(conf.server.base_url.clone());
 
+    // Get inbound webhook and admin credentials from configuration
+    let webhooks = conf.webhooks.clone().map(Arc::new);
+    let admin = conf.admin.clone().map(Arc::new);
+
+    // Create the rate limiter shared by all requests
+    let rate_limiter = Arc::new(SubscriptionRateLimiter::new(&conf.server.rate_limit));
+
+    // Create the sign-up bot protection
+    let bot_protection = Arc::new(
+        BotProtection::new(&conf.bot_protection).context("Invalid bot_protection settings")?,
+    );
+
+    // Create the sign-up email domain check
+    let email_domains = Arc::new(
+        EmailDomainCheck::new(&conf.email_domains).context("Invalid email_domains settings")?,
+    );
+
+    // Get the confirmation link settings and the HTML page templates
+    let confirmation = Arc::new(conf.confirmation.clone());
+    let templates =
+        Arc::new(Templates::new(&conf.templates).context("Invalid templates settings")?);
+
+    // Create the CSRF and flash message cookies of the hosted subscription form
+    let subscribe_form = Arc::new(SubscribeForm::new(
+        &conf.subscribe_form,
+        &conf.server.base_url,
+    ));
+
     // Return the application state with all components
-    AppState {
+    Ok(AppState {
         db,
         email_client,
         base_url,
-    }
+        webhooks,
+        admin,
+        rate_limiter,
+        bot_protection,
+        email_domains,
+        confirmation,
+        templates,
+        subscribe_form,
+    })
 }
@@ -0,0 +1,296 @@
+use axum::http::{header, HeaderMap, HeaderValue};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use rand::{distr::Alphanumeric, rng, Rng};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::configuration::SubscribeFormSettings;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Cookie holding the CSRF token of the visitor
+const CSRF_COOKIE: &str = "newsletter_csrf";
+
+/// Cookie holding the message shown on the next rendering of the form
+const FLASH_COOKIE: &str = "newsletter_flash";
+
+/// Length of a CSRF token, in alphanumeric characters
+const CSRF_TOKEN_LENGTH: usize = 32;
+
+/// Message shown once, on the next rendering of the form
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Flash {
+    pub level: FlashLevel,
+    pub message: String,
+}
+
+impl Flash {
+    pub fn success(message: impl Into<String>) -> Self {
+        Self {
+            level: FlashLevel::Success,
+            message: message.into(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            level: FlashLevel::Error,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FlashLevel {
+    Success,
+    Error,
+}
+
+impl FlashLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Error => "error",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "success" => Some(Self::Success),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+/// The submitted CSRF token doesn't match the cookie of the visitor
+#[derive(thiserror::Error, Debug)]
+#[error("The form has expired, reload the page and submit it again.")]
+pub struct CsrfRejected;
+
+/// Protects the hosted subscription form with signed cookies
+///
+/// The CSRF token is kept in a signed cookie and repeated in a hidden form field,
+/// a submission is only accepted when both match.
+pub struct SubscribeForm {
+    signing_key: SecretString,
+    /// Only send the cookies over HTTPS
+    secure: bool,
+}
+
+impl SubscribeForm {
+    /// Creates a new form protection from configuration settings
+    ///
+    /// # Arguments
+    /// * `conf` - Subscription form settings
+    /// * `base_url` - Public URL of the service, cookies are marked `Secure` over HTTPS
+    pub fn new(conf: &SubscribeFormSettings, base_url: &str) -> Self {
+        Self {
+            signing_key: conf.signing_key.clone(),
+            secure: base_url.starts_with("https://"),
+        }
+    }
+
+    /// Returns the CSRF token of the visitor
+    ///
+    /// # Returns
+    /// The token, along with the `Set-Cookie` header to send when a new one was issued
+    pub fn csrf_token(&self, headers: &HeaderMap) -> (String, Option<HeaderValue>) {
+        if let Some(token) = self.signed_cookie(headers, CSRF_COOKIE) {
+            return (token.to_string(), None);
+        }
+        let mut rng = rng();
+        let token: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+            .map(char::from)
+            .take(CSRF_TOKEN_LENGTH)
+            .collect();
+        let cookie = self.set_cookie(CSRF_COOKIE, &token);
+        (token, Some(cookie))
+    }
+
+    /// Checks a submitted CSRF token against the cookie of the visitor
+    ///
+    /// # Returns
+    /// Ok(()) if the token matches the cookie, CsrfRejected otherwise
+    pub fn verify_csrf_token(&self, headers: &HeaderMap, token: &str) -> Result<(), CsrfRejected> {
+        let cookie = read_cookie(headers, CSRF_COOKIE).ok_or(CsrfRejected)?;
+        let (_, signature) = cookie.rsplit_once('.').ok_or(CsrfRejected)?;
+        // Checking the signature against the submitted token compares both in constant time
+        self.verify(CSRF_COOKIE, token, signature)
+            .then_some(())
+            .ok_or(CsrfRejected)
+    }
+
+    /// Returns the `Set-Cookie` header storing a flash message
+    pub fn flash_cookie(&self, flash: &Flash) -> HeaderValue {
+        let value = format!(
+            "{}.{}",
+            flash.level.as_str(),
+            URL_SAFE_NO_PAD.encode(&flash.message)
+        );
+        self.set_cookie(FLASH_COOKIE, &value)
+    }
+
+    /// Returns the flash message of the visitor, if any
+    pub fn flash(&self, headers: &HeaderMap) -> Option<Flash> {
+        let value = self.signed_cookie(headers, FLASH_COOKIE)?;
+        let (level, message) = value.split_once('.')?;
+        let message = URL_SAFE_NO_PAD.decode(message).ok()?;
+        Some(Flash {
+            level: FlashLevel::parse(level)?,
+            message: String::from_utf8(message).ok()?,
+        })
+    }
+
+    /// Returns the `Set-Cookie` header deleting the flash message once shown
+    pub fn clear_flash_cookie(&self) -> HeaderValue {
+        HeaderValue::from_str(&format!(
+            "{}=; Max-Age=0{}",
+            FLASH_COOKIE,
+            self.cookie_attributes()
+        ))
+        .expect("Cookie names are valid header values")
+    }
+
+    fn mac(&self, name: &str, value: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.signing_key.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        // Sign the name too, so that a cookie can't be passed off as another one
+        mac.update(name.as_bytes());
+        mac.update(b"=");
+        mac.update(value.as_bytes());
+        mac
+    }
+
+    fn verify(&self, name: &str, value: &str, signature: &str) -> bool {
+        hex::decode(signature)
+            .is_ok_and(|signature| self.mac(name, value).verify_slice(&signature).is_ok())
+    }
+
+    /// Returns the value of a cookie if its signature is valid
+    fn signed_cookie<'a>(&self, headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+        let (value, signature) = read_cookie(headers, name)?.rsplit_once('.')?;
+        self.verify(name, value, signature).then_some(value)
+    }
+
+    fn set_cookie(&self, name: &str, value: &str) -> HeaderValue {
+        let signature = hex::encode(self.mac(name, value).finalize().into_bytes());
+        HeaderValue::from_str(&format!(
+            "{}={}.{}{}",
+            name,
+            value,
+            signature,
+            self.cookie_attributes()
+        ))
+        .expect("Signed cookie values are valid header values")
+    }
+
+    fn cookie_attributes(&self) -> &'static str {
+        if self.secure {
+            "; Path=/; HttpOnly; SameSite=Lax; Secure"
+        } else {
+            "; Path=/; HttpOnly; SameSite=Lax"
+        }
+    }
+}
+
+/// Reads a cookie sent by the client
+fn read_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_none, assert_ok, assert_some_eq};
+
+    fn subscribe_form(signing_key: &str) -> SubscribeForm {
+        SubscribeForm::new(
+            &SubscribeFormSettings {
+                signing_key: SecretString::from(signing_key),
+            },
+            "http://localhost",
+        )
+    }
+
+    /// Returns the headers of a request sending back a cookie
+    fn sending(set_cookie: &HeaderValue) -> HeaderMap {
+        let (cookie, _) = set_cookie.to_str().unwrap().split_once(';').unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_str(cookie).unwrap());
+        headers
+    }
+
+    #[test]
+    fn the_issued_csrf_token_matches_its_cookie() {
+        let form = subscribe_form("signing-key");
+        let (token, cookie) = form.csrf_token(&HeaderMap::new());
+        let headers = sending(&cookie.unwrap());
+        assert_ok!(form.verify_csrf_token(&headers, &token));
+    }
+
+    #[test]
+    fn the_csrf_token_of_a_returning_visitor_is_kept() {
+        let form = subscribe_form("signing-key");
+        let (token, cookie) = form.csrf_token(&HeaderMap::new());
+        let (again, cookie_again) = form.csrf_token(&sending(&cookie.unwrap()));
+        assert_eq!(token, again);
+        assert_none!(cookie_again);
+    }
+
+    #[test]
+    fn a_csrf_token_without_cookie_is_rejected() {
+        let form = subscribe_form("signing-key");
+        let (token, _) = form.csrf_token(&HeaderMap::new());
+        assert_err!(form.verify_csrf_token(&HeaderMap::new(), &token));
+    }
+
+    #[test]
+    fn a_csrf_token_of_another_visitor_is_rejected() {
+        let form = subscribe_form("signing-key");
+        let (_, cookie) = form.csrf_token(&HeaderMap::new());
+        let (other_token, _) = form.csrf_token(&HeaderMap::new());
+        assert_err!(form.verify_csrf_token(&sending(&cookie.unwrap()), &other_token));
+    }
+
+    #[test]
+    fn a_csrf_cookie_signed_with_another_key_is_rejected() {
+        let (token, cookie) = subscribe_form("another-key").csrf_token(&HeaderMap::new());
+        let headers = sending(&cookie.unwrap());
+        assert_err!(subscribe_form("signing-key").verify_csrf_token(&headers, &token));
+    }
+
+    #[test]
+    fn a_flash_message_round_trips_through_its_cookie() {
+        let form = subscribe_form("signing-key");
+        let flash = Flash::error("The name contains a forbidden character ';'.");
+        let headers = sending(&form.flash_cookie(&flash));
+        assert_some_eq!(form.flash(&headers), flash);
+    }
+
+    #[test]
+    fn a_tampered_flash_message_is_ignored() {
+        let form = subscribe_form("signing-key");
+        let cookie = form.flash_cookie(&Flash::error("Oops"));
+        let (cookie, _) = cookie.to_str().unwrap().split_once(';').unwrap();
+        let (_, signature) = cookie.rsplit_once('.').unwrap();
+        let forged = format!(
+            "{}=success.{}.{}; Path=/",
+            FLASH_COOKIE,
+            URL_SAFE_NO_PAD.encode("Oops"),
+            signature
+        );
+        let headers = sending(&HeaderValue::from_str(&forged).unwrap());
+        assert_none!(form.flash(&headers));
+    }
+}
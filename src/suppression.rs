@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+
+use crate::{domain::SubscriberEmail, router::DbPool};
+
+/// An address that must never be mailed
+#[derive(serde::Serialize)]
+pub struct Suppression {
+    pub email: String,
+    pub reason: String,
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Checks whether an email address is on the suppression list
+///
+/// Addresses are stored and compared in their normalized form.
+///
+/// # Arguments
+/// * `pool` - Database pool
+/// * `email` - Email address to look up
+///
+/// # Returns
+/// true if the address must not be mailed
+#[instrument(name = "Check the suppression list", skip_all)]
+pub async fn is_suppressed(pool: &DbPool, email: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM suppressions WHERE email = $1) AS "suppressed!""#,
+        SubscriberEmail::normalize(email)
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(result.suppressed)
+}
+
+/// Adds an email address to the suppression list
+///
+/// If the address is already suppressed its reason and source are updated.
+///
+/// # Arguments
+/// * `pool` - Database pool
+/// * `email` - Email address to suppress
+/// * `reason` - Why the address is suppressed
+/// * `source` - Who suppressed the address (e.g. `admin`, `postmark`)
+#[instrument(name = "Add an address to the suppression list", skip(pool, email))]
+pub async fn suppress(
+    pool: &DbPool,
+    email: &str,
+    reason: &str,
+    source: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO suppressions (email, reason, source, created_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (email) DO UPDATE SET reason = EXCLUDED.reason, source = EXCLUDED.source"#,
+        SubscriberEmail::normalize(email),
+        reason,
+        source,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Removes an email address from the suppression list
+///
+/// # Returns
+/// true if the address was suppressed
+#[instrument(name = "Remove an address from the suppression list", skip_all)]
+pub async fn unsuppress(pool: &DbPool, email: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM suppressions WHERE email = $1"#,
+        SubscriberEmail::normalize(email)
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Lists every suppressed address, most recent first
+#[instrument(name = "List the suppression list", skip_all)]
+pub async fn list_suppressions(pool: &DbPool) -> Result<Vec<Suppression>, sqlx::Error> {
+    sqlx::query_as!(
+        Suppression,
+        r#"SELECT email, reason, source, created_at FROM suppressions ORDER BY created_at DESC"#
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,122 @@
+use anyhow::Context;
+use minijinja::{value::Serde, Environment};
+use serde::Serialize;
+
+use crate::configuration::TemplateSettings;
+
+/// HTML templates built into the binary, by name
+const BUILT_IN_TEMPLATES: [(&str, &str); 3] = [
+    ("layout.html", include_str!("../templates/layout.html")),
+    (
+        "confirmation.html",
+        include_str!("../templates/confirmation.html"),
+    ),
+    (
+        "subscribe.html",
+        include_str!("../templates/subscribe.html"),
+    ),
+];
+
+/// Renders the HTML pages served to browsers
+///
+/// Values are HTML-escaped in every template ending in `.html`.
+pub struct Templates {
+    env: Environment<'static>,
+}
+
+impl Templates {
+    /// Loads the built-in templates, replaced by the ones of the configured directory
+    ///
+    /// # Arguments
+    /// * `conf` - Template settings
+    ///
+    /// # Returns
+    /// The loaded templates if successful, Error if a template can't be read or doesn't compile
+    pub fn new(conf: &TemplateSettings) -> anyhow::Result<Self> {
+        let mut env = Environment::new();
+        for (name, built_in) in BUILT_IN_TEMPLATES {
+            let path = conf.dir.as_ref().map(|dir| dir.join(name));
+            let source = match path {
+                Some(path) if path.exists() => std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read template {}", path.display()))?,
+                _ => built_in.to_string(),
+            };
+            env.add_template_owned(name, source)
+                .with_context(|| format!("Invalid template {}", name))?;
+        }
+        Ok(Self { env })
+    }
+
+    /// Renders a template
+    ///
+    /// # Arguments
+    /// * `name` - Name of the template
+    /// * `context` - Values available to the template
+    ///
+    /// # Returns
+    /// The rendered page if successful, Error otherwise
+    pub fn render(&self, name: &str, context: impl Serialize) -> Result<String, minijinja::Error> {
+        self.env.get_template(name)?.render(Serde(context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::assert_ok;
+
+    #[test]
+    fn every_confirmation_outcome_has_a_title() {
+        let templates = Templates::new(&TemplateSettings::default()).unwrap();
+        for outcome in [
+            "confirmed",
+            "already_confirmed",
+            "expired",
+            "unknown_token",
+            "error",
+        ] {
+            let page =
+                assert_ok!(templates.render("confirmation.html", minijinja::context! { outcome }));
+            let title = page
+                .split_once("<title>")
+                .and_then(|(_, rest)| rest.split_once("</title>"))
+                .map(|(title, _)| title.trim())
+                .unwrap();
+            assert!(!title.is_empty(), "No title for {}", outcome);
+        }
+    }
+
+    #[test]
+    fn templates_of_the_configured_directory_replace_the_built_in_ones() {
+        let dir = std::env::temp_dir().join(format!("templates-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("layout.html"),
+            "<p>{% block content %}{% endblock %}</p>",
+        )
+        .unwrap();
+        let templates = Templates::new(&TemplateSettings {
+            dir: Some(dir.clone()),
+        })
+        .unwrap();
+        let page = assert_ok!(templates.render(
+            "confirmation.html",
+            minijinja::context! { outcome => "confirmed" }
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(page.starts_with("<p>"));
+        assert!(page.contains("You're in!"));
+    }
+
+    #[test]
+    fn an_invalid_template_is_reported() {
+        let dir = std::env::temp_dir().join(format!("templates-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("subscribe.html"), "{% block content %}").unwrap();
+        let result = Templates::new(&TemplateSettings {
+            dir: Some(dir.clone()),
+        });
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(result.is_err());
+    }
+}
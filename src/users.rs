@@ -0,0 +1,280 @@
+use anyhow::Context;
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
+    PasswordVerifier, Version,
+};
+use chrono::{DateTime, Utc};
+use rand::{rng, Rng};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Serialize;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::router::DbPool;
+
+/// Hash verified when the username is unknown, so that the response time doesn't tell
+/// whether a user exists
+const DUMMY_PASSWORD_HASH: &str = "$argon2id$v=19$m=15000,t=2,p=1$\
+    gZiV/M1gPc22ElAH/Jh1Hw$CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno";
+
+/// Role of an admin user, granting a fixed set of permissions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// Everything, including managing users and API keys
+    Owner,
+    /// Drafts and publishes newsletter issues
+    Editor,
+    /// Reads the delivery statistics
+    Analyst,
+    /// Manages subscribers
+    Support,
+}
+
+impl Role {
+    /// Returns the role as stored and sent to clients
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Editor => "editor",
+            Self::Analyst => "analyst",
+            Self::Support => "support",
+        }
+    }
+
+    /// Parses a role, returning None if it is unknown
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "owner" => Some(Self::Owner),
+            "editor" => Some(Self::Editor),
+            "analyst" => Some(Self::Analyst),
+            "support" => Some(Self::Support),
+            _ => None,
+        }
+    }
+
+    /// Tells whether the role grants a permission
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        match self {
+            Self::Owner => true,
+            Self::Editor => permission == Permission::PublishNewsletters,
+            Self::Analyst => permission == Permission::ReadStats,
+            Self::Support => permission == Permission::ManageSubscribers,
+        }
+    }
+}
+
+/// Operation on the admin routes a role may be granted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+    ManageUsers,
+    ManageApiKeys,
+    PublishNewsletters,
+    ReadStats,
+    ManageSubscribers,
+}
+
+impl Permission {
+    /// Returns the permission as shown in error messages
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ManageUsers => "users:manage",
+            Self::ManageApiKeys => "api_keys:manage",
+            Self::PublishNewsletters => "newsletters:publish",
+            Self::ReadStats => "stats:read",
+            Self::ManageSubscribers => "subscribers:manage",
+        }
+    }
+}
+
+/// An admin user as listed to owners, without the password hash
+#[derive(Serialize)]
+pub struct User {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The admin user a request authenticated as
+#[derive(Clone, Debug)]
+pub struct AdminUser {
+    pub username: String,
+    pub role: Role,
+}
+
+/// Hashes a password with Argon2id and a random salt
+fn hash_password(password: &SecretString) -> Result<String, anyhow::Error> {
+    let salt: [u8; 16] = rng().random();
+    let salt = SaltString::encode_b64(&salt).map_err(|e| anyhow::anyhow!(e))?;
+    let hash = argon2()
+        .hash_password(password.expose_secret().as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!(e))
+        .context("Failed to hash the password.")?;
+    Ok(hash.to_string())
+}
+
+/// Tells whether a password matches a stored hash
+fn verify_password(password: &SecretString, password_hash: &str) -> Result<bool, anyhow::Error> {
+    let password_hash = PasswordHash::new(password_hash)
+        .map_err(|e| anyhow::anyhow!(e))
+        .context("Failed to parse the stored password hash.")?;
+    Ok(argon2()
+        .verify_password(password.expose_secret().as_bytes(), &password_hash)
+        .is_ok())
+}
+
+fn argon2() -> Argon2<'static> {
+    Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(15000, 2, 1, None).expect("The Argon2 parameters are valid"),
+    )
+}
+
+/// Creates a new admin user
+///
+/// # Arguments
+/// * `pool` - Database pool
+/// * `username` - Name the user signs in with
+/// * `password` - Password the user signs in with, only its hash is stored
+/// * `role` - Role granting the user its permissions
+///
+/// # Returns
+/// The stored user, or None if the username is taken
+#[instrument(name = "Create a user", skip(pool, password))]
+pub async fn create_user(
+    pool: &DbPool,
+    username: &str,
+    password: SecretString,
+    role: Role,
+) -> Result<Option<User>, anyhow::Error> {
+    // Hashing is CPU-bound, keep it off the async workers
+    let password_hash = tokio::task::spawn_blocking(move || hash_password(&password))
+        .await
+        .context("Failed to spawn the password hashing task.")??;
+    let user = sqlx::query_as!(
+        User,
+        r#"INSERT INTO users (user_id, username, password_hash, role, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (username) DO NOTHING
+        RETURNING user_id, username, role, created_at"#,
+        Uuid::new_v4(),
+        username,
+        password_hash,
+        role.as_str(),
+        Utc::now()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to store the user.")?;
+    Ok(user)
+}
+
+/// Lists every admin user, by username
+#[instrument(name = "List users", skip_all)]
+pub async fn list_users(pool: &DbPool) -> Result<Vec<User>, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        r#"SELECT user_id, username, role, created_at FROM users ORDER BY username"#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Deletes an admin user, it can't sign in anymore
+///
+/// # Returns
+/// true if the user existed
+#[instrument(name = "Delete a user", skip(pool))]
+pub async fn delete_user(pool: &DbPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(r#"DELETE FROM users WHERE user_id = $1"#, user_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Checks the credentials of an admin user
+///
+/// # Returns
+/// The user if the password matches, None otherwise
+#[instrument(name = "Authenticate a user", skip(pool, password))]
+pub async fn authenticate(
+    pool: &DbPool,
+    username: &str,
+    password: SecretString,
+) -> Result<Option<AdminUser>, anyhow::Error> {
+    let stored = sqlx::query!(
+        r#"SELECT username, password_hash, role FROM users WHERE username = $1"#,
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the user.")?;
+
+    let password_hash = stored
+        .as_ref()
+        .map_or(DUMMY_PASSWORD_HASH.to_string(), |user| {
+            user.password_hash.clone()
+        });
+    let matches = tokio::task::spawn_blocking(move || verify_password(&password, &password_hash))
+        .await
+        .context("Failed to spawn the password verification task.")??;
+
+    match stored {
+        Some(user) if matches => {
+            let role = Role::parse(&user.role).context("The stored role is unknown.")?;
+            Ok(Some(AdminUser {
+                username: user.username,
+                role,
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROLES: [Role; 4] = [Role::Owner, Role::Editor, Role::Analyst, Role::Support];
+
+    #[test]
+    fn roles_round_trip_through_their_string_form() {
+        for role in ROLES {
+            assert_eq!(Role::parse(role.as_str()), Some(role));
+        }
+        assert_eq!(Role::parse("admin"), None);
+    }
+
+    #[test]
+    fn only_owners_manage_users_and_api_keys() {
+        for role in ROLES {
+            let is_owner = role == Role::Owner;
+            assert_eq!(role.has_permission(Permission::ManageUsers), is_owner);
+            assert_eq!(role.has_permission(Permission::ManageApiKeys), is_owner);
+        }
+    }
+
+    #[test]
+    fn each_staff_role_is_granted_its_own_permission() {
+        assert!(Role::Editor.has_permission(Permission::PublishNewsletters));
+        assert!(!Role::Editor.has_permission(Permission::ManageSubscribers));
+        assert!(Role::Analyst.has_permission(Permission::ReadStats));
+        assert!(!Role::Analyst.has_permission(Permission::PublishNewsletters));
+        assert!(Role::Support.has_permission(Permission::ManageSubscribers));
+        assert!(!Role::Support.has_permission(Permission::ReadStats));
+    }
+
+    #[test]
+    fn passwords_are_verified_against_their_hash() {
+        let password = SecretString::from("correct horse battery staple");
+        let hash = hash_password(&password).unwrap();
+        assert!(verify_password(&password, &hash).unwrap());
+        assert!(!verify_password(&SecretString::from("wrong"), &hash).unwrap());
+    }
+
+    #[test]
+    fn the_dummy_hash_is_a_valid_hash() {
+        assert!(!verify_password(&SecretString::from("anything"), DUMMY_PASSWORD_HASH).unwrap());
+    }
+}
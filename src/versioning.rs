@@ -0,0 +1,77 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::configuration::LegacyRoutesSettings;
+
+/// Prefix of the routes of version 1 of the API
+pub const API_V1: &str = "/api/v1";
+
+/// Announces that a resource is deprecated, RFC 9745
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+
+/// Announces when a resource stops responding, RFC 8594
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// Deprecation headers sent by the unversioned paths kept as aliases of a version
+pub struct LegacyRoutes {
+    deprecation: HeaderValue,
+    sunset: HeaderValue,
+    /// Prefix of the version the legacy paths are an alias of
+    successor: &'static str,
+}
+
+impl LegacyRoutes {
+    /// Creates the deprecation headers from configuration settings
+    ///
+    /// # Arguments
+    /// * `conf` - Legacy routes settings
+    /// * `successor` - Prefix of the version the legacy paths are an alias of
+    pub fn new(conf: &LegacyRoutesSettings, successor: &'static str) -> Self {
+        if conf.sunset_at <= Utc::now() {
+            warn!(
+                sunset_at = %conf.sunset_at,
+                "The sunset date of the legacy routes has passed, remove them or postpone it"
+            );
+        }
+        let deprecation = format!("@{}", conf.deprecated_at.timestamp());
+        let sunset = conf
+            .sunset_at
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string();
+        Self {
+            deprecation: HeaderValue::from_str(&deprecation)
+                .expect("Timestamps are valid header values"),
+            sunset: HeaderValue::from_str(&sunset).expect("HTTP dates are valid header values"),
+            successor,
+        }
+    }
+}
+
+/// Marks the responses of legacy paths as deprecated, linking to their versioned successor
+pub async fn deprecate_legacy_route(
+    State(legacy): State<Arc<LegacyRoutes>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let successor = format!(
+        "<{}{}>; rel=\"successor-version\"",
+        legacy.successor,
+        request.uri().path()
+    );
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(DEPRECATION, legacy.deprecation.clone());
+    headers.insert(SUNSET, legacy.sunset.clone());
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.append(header::LINK, link);
+    }
+    response
+}
@@ -0,0 +1,210 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app};
+
+#[tokio::test]
+async fn a_created_api_key_is_only_shown_once() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .post_admin_api_keys(&serde_json::json!({
+            "name": "publisher",
+            "scopes": ["newsletters:publish"],
+        }))
+        .await;
+
+    // assert
+    assert_eq!(201, response.status().as_u16());
+    let created: serde_json::Value = response.json().await.unwrap();
+    let key = created["key"].as_str().unwrap();
+    assert!(key.starts_with("nl_"));
+    assert_eq!(
+        created["scopes"],
+        serde_json::json!(["newsletters:publish"])
+    );
+
+    let listed: serde_json::Value = app.get_admin_api_keys().await.json().await.unwrap();
+    let listed = listed
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|api_key| api_key["id"] == created["id"])
+        .unwrap();
+    assert_eq!(listed["name"], "publisher");
+    assert!(listed.get("key").is_none());
+    assert!(key.starts_with(listed["key_prefix"].as_str().unwrap()));
+    let stored = sqlx::query!("SELECT key_hash FROM api_keys WHERE name = 'publisher'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.key_hash, key);
+}
+
+#[tokio::test]
+async fn using_an_api_key_records_when_it_was_last_used() {
+    // init
+    let app = spawn_app().await;
+    let key = app.create_api_key(&["subscribers:read"]).await;
+
+    // execute
+    app.get_subscribers(&key, "")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // assert
+    let listed: serde_json::Value = app.get_admin_api_keys().await.json().await.unwrap();
+    let listed = listed
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|api_key| key.starts_with(api_key["key_prefix"].as_str().unwrap()))
+        .unwrap();
+    assert!(listed["last_used_at"].is_string());
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_api_key_are_rejected_with_a_401() {
+    // init
+    let app = spawn_app().await;
+    let test_cases = vec![(None, "missing key"), (Some("nl_unknown"), "unknown key")];
+
+    for (key, description) in test_cases {
+        // execute
+        let mut request = app
+            .http_client
+            .post(format!("{}/api/v1/newsletters", app.address))
+            .json(&serde_json::json!({
+                "title": "Newsletter title",
+                "content": {"text": "Body", "html": "<p>Body</p>"},
+            }));
+        if let Some(key) = key {
+            request = request.bearer_auth(key);
+        }
+        let response = request.send().await.expect("Failed to execute request.");
+
+        // assert
+        assert_eq!(
+            401,
+            response.status().as_u16(),
+            "The API did not reject a request with a {}",
+            description
+        );
+        assert_eq!("Bearer", response.headers()["WWW-Authenticate"]);
+    }
+}
+
+#[tokio::test]
+async fn an_api_key_without_the_scope_of_the_route_is_rejected_with_a_403() {
+    // init
+    let app = spawn_app().await;
+    let key = app.create_api_key(&["newsletters:publish"]).await;
+
+    // execute
+    let response = app.get_subscribers(&key, "").await;
+
+    // assert
+    assert_eq!(403, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "auth.forbidden");
+}
+
+#[tokio::test]
+async fn a_revoked_api_key_is_rejected() {
+    // init
+    let app = spawn_app().await;
+    let response = app
+        .post_admin_api_keys(&serde_json::json!({
+            "name": "reader",
+            "scopes": ["subscribers:read"],
+        }))
+        .await;
+    let created: serde_json::Value = response.json().await.unwrap();
+    let id = created["id"].as_str().unwrap();
+    let key = created["key"].as_str().unwrap();
+
+    // execute
+    let revoked = app.delete_admin_api_key(id).await;
+    let revoked_again = app.delete_admin_api_key(id).await;
+    let response = app.get_subscribers(key, "").await;
+
+    // assert
+    assert_eq!(200, revoked.status().as_u16());
+    assert_eq!(404, revoked_again.status().as_u16());
+    let body: serde_json::Value = revoked_again.json().await.unwrap();
+    assert_eq!(body["code"], "api_key.not_found");
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn creating_an_api_key_returns_400_for_invalid_data() {
+    // init
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"name": " ", "scopes": ["subscribers:read"]}),
+            "api_key.name_empty",
+            "empty name",
+        ),
+        (
+            serde_json::json!({"name": "reader", "scopes": []}),
+            "api_key.scopes_empty",
+            "no scopes",
+        ),
+        (
+            serde_json::json!({"name": "reader", "scopes": ["subscribers:write"]}),
+            "api_key.scope_unknown",
+            "unknown scope",
+        ),
+    ];
+
+    for (body, code, description) in test_cases {
+        // execute
+        let response = app.post_admin_api_keys(&body).await;
+
+        // assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not fail with 400 Bad Request when the payload had {}.",
+            description
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["errors"][0]["code"], code);
+    }
+}
+
+#[tokio::test]
+async fn subscribers_are_listed_with_the_read_scope() {
+    // init
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let key = app.create_api_key(&["subscribers:read"]).await;
+
+    // execute
+    let response = app.get_subscribers(&key, "status=confirmed").await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["status"], "confirmed");
+}
+
+#[tokio::test]
+async fn revoking_an_api_key_returns_a_problem_document_for_a_malformed_id() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app.delete_admin_api_key("not-a-uuid").await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "request.malformed");
+}
@@ -0,0 +1,199 @@
+use crate::helpers::{spawn_app, spawn_app_with};
+use newsletter::configuration::CaptchaSettings;
+use secrecy::SecretString;
+use wiremock::matchers::{any, body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Settings pointing the captcha verifier at a mock server.
+fn captcha_settings(server: &MockServer) -> CaptchaSettings {
+    CaptchaSettings {
+        verify_url: format!("{}/siteverify", server.uri()),
+        secret: SecretString::from("captcha-secret"),
+        timeout_millis: 2000,
+    }
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_when_the_honeypot_is_filled() {
+    // init
+    let app = spawn_app().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=vic%20ji&email=vic_ji_i%40gmail.com&website=spam.example.com")
+        .await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_without_a_form_token_when_a_minimum_time_is_required() {
+    // init
+    let app = spawn_app_with(|c| c.bot_protection.min_submit_secs = 60).await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=vic%20ji&email=vic_ji_i%40gmail.com")
+        .await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_when_the_form_is_submitted_too_quickly() {
+    // init
+    let app = spawn_app_with(|c| c.bot_protection.min_submit_secs = 60).await;
+    let body: serde_json::Value = app
+        .http_client
+        .get(format!("{}/api/v1/subscriptions/form_token", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+    let form_token = body["form_token"].as_str().unwrap();
+
+    // execute
+    let response = app
+        .post_subscriptions(&format!(
+            "name=vic%20ji&email=vic_ji_i%40gmail.com&form_token={}",
+            form_token
+        ))
+        .await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_when_a_form_token_is_used_again() {
+    // init
+    let app = spawn_app_with(|c| c.bot_protection.min_submit_secs = 1).await;
+    let body: serde_json::Value = app
+        .http_client
+        .get(format!("{}/api/v1/subscriptions/form_token", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+    let form_token = body["form_token"].as_str().unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let first = app
+        .post_subscriptions(&format!(
+            "name=vic%20ji&email=vic_ji_i%40gmail.com&form_token={}",
+            form_token
+        ))
+        .await;
+    let second = app
+        .post_subscriptions(&format!(
+            "name=ursula&email=ursula_le_guin%40gmail.com&form_token={}",
+            form_token
+        ))
+        .await;
+
+    // assert
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(400, second.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_returns_200_when_the_captcha_is_solved() {
+    // init
+    let captcha_server = MockServer::start().await;
+    let settings = captcha_settings(&captcha_server);
+    let app = spawn_app_with(|c| c.bot_protection.captcha = Some(settings)).await;
+
+    Mock::given(path("/siteverify"))
+        .and(method("POST"))
+        .and(body_string_contains("secret=captcha-secret"))
+        .and(body_string_contains("response=captcha-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "success": true
+        })))
+        .expect(1)
+        .mount(&captcha_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions(
+            "name=vic%20ji&email=vic_ji_i%40gmail.com&captcha_response=captcha-token",
+        )
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_when_the_captcha_verification_fails() {
+    // init
+    let captcha_server = MockServer::start().await;
+    let settings = captcha_settings(&captcha_server);
+    let app = spawn_app_with(|c| c.bot_protection.captcha = Some(settings)).await;
+
+    Mock::given(path("/siteverify"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "success": false,
+            "error-codes": ["invalid-input-response"]
+        })))
+        .expect(1)
+        .mount(&captcha_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=vic%20ji&email=vic_ji_i%40gmail.com&captcha_response=bad-token")
+        .await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_when_the_captcha_response_is_missing() {
+    // init
+    let captcha_server = MockServer::start().await;
+    let settings = captcha_settings(&captcha_server);
+    let app = spawn_app_with(|c| c.bot_protection.captcha = Some(settings)).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&captcha_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=vic%20ji&email=vic_ji_i%40gmail.com")
+        .await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+}
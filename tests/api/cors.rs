@@ -0,0 +1,90 @@
+use crate::helpers::{spawn_app_with, TestApp};
+use reqwest::{Method, Response};
+
+const ORIGIN: &str = "https://marketing.example.com";
+
+async fn spawn_app_allowing_origin() -> TestApp {
+    spawn_app_with(|c| c.server.cors.allowed_origins = vec![ORIGIN.to_string()]).await
+}
+
+async fn preflight(app: &TestApp, path: &str, origin: &str) -> Response {
+    app.http_client
+        .request(Method::OPTIONS, format!("{}{}", app.address, path))
+        .header("Origin", origin)
+        .header("Access-Control-Request-Method", "POST")
+        .header("Access-Control-Request-Headers", "content-type")
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn preflight_requests_from_an_allowed_origin_are_accepted() {
+    // init
+    let app = spawn_app_allowing_origin().await;
+
+    // execute
+    let response = preflight(&app, "/api/v1/subscriptions", ORIGIN).await;
+
+    // assert
+    assert!(response.status().is_success());
+    let headers = response.headers();
+    assert_eq!(headers["Access-Control-Allow-Origin"], ORIGIN);
+    assert!(headers["Access-Control-Allow-Methods"]
+        .to_str()
+        .unwrap()
+        .contains("POST"));
+    assert!(headers["Access-Control-Allow-Headers"]
+        .to_str()
+        .unwrap()
+        .contains("content-type"));
+}
+
+#[tokio::test]
+async fn responses_to_an_allowed_origin_carry_the_cors_headers() {
+    // init
+    let app = spawn_app_allowing_origin().await;
+
+    // execute
+    let response = app
+        .http_client
+        .post(format!("{}/api/v1/subscriptions", app.address))
+        .header("Origin", ORIGIN)
+        .json(&serde_json::json!({ "name": "", "email": "" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(response.headers()["Access-Control-Allow-Origin"], ORIGIN);
+}
+
+#[tokio::test]
+async fn other_origins_are_not_allowed() {
+    // init
+    let app = spawn_app_allowing_origin().await;
+
+    // execute
+    let response = preflight(&app, "/api/v1/subscriptions", "https://evil.example.com").await;
+
+    // assert
+    assert!(response
+        .headers()
+        .get("Access-Control-Allow-Origin")
+        .is_none());
+}
+
+#[tokio::test]
+async fn admin_routes_do_not_allow_cross_origin_requests() {
+    // init
+    let app = spawn_app_allowing_origin().await;
+
+    // execute
+    let response = preflight(&app, "/api/v1/admin/suppressions", ORIGIN).await;
+
+    // assert
+    assert!(response
+        .headers()
+        .get("Access-Control-Allow-Origin")
+        .is_none());
+}
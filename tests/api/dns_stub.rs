@@ -0,0 +1,103 @@
+use hickory_resolver::proto::{
+    op::{Message, ResponseCode},
+    rr::{
+        rdata::{A, MX},
+        Name, RData, Record, RecordType,
+    },
+};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::net::UdpSocket;
+
+/// An in-process DNS server answering from a fixed set of records
+///
+/// Names without any record are answered with `NXDOMAIN`, names with records of
+/// other types only with an empty answer.
+#[derive(Clone)]
+pub struct DnsStub {
+    address: SocketAddr,
+    records: Arc<Mutex<HashMap<Name, Vec<RData>>>>,
+}
+
+impl DnsStub {
+    /// Starts the stub on a random local UDP port
+    pub async fn start() -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind the DNS stub");
+        let stub = Self {
+            address: socket.local_addr().unwrap(),
+            records: Arc::default(),
+        };
+
+        let server = stub.clone();
+        tokio::spawn(async move {
+            let mut buffer = [0; 4096];
+            while let Ok((len, peer)) = socket.recv_from(&mut buffer).await {
+                if let Some(response) = server.answer(&buffer[..len]) {
+                    let _ = socket.send_to(&response, peer).await;
+                }
+            }
+        });
+        stub
+    }
+
+    /// Returns the address the stub is listening on
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Adds an MX record, an exchange of "." being a null MX
+    pub fn add_mx(&self, domain: &str, exchange: &str) {
+        self.add(domain, RData::MX(MX::new(10, fqdn(exchange))));
+    }
+
+    /// Adds an A record
+    pub fn add_a(&self, domain: &str, ip: Ipv4Addr) {
+        self.add(domain, RData::A(A(ip)));
+    }
+
+    fn add(&self, domain: &str, rdata: RData) {
+        self.records
+            .lock()
+            .unwrap()
+            .entry(fqdn(domain))
+            .or_default()
+            .push(rdata);
+    }
+
+    fn answer(&self, request: &[u8]) -> Option<Vec<u8>> {
+        let request = Message::from_vec(request).ok()?;
+        let query = request.queries.first()?.clone();
+        let mut response = Message::response(request.metadata.id, request.metadata.op_code);
+        response.metadata.recursion_desired = request.metadata.recursion_desired;
+        response.metadata.recursion_available = true;
+        response.add_query(query.clone());
+
+        let records = self.records.lock().unwrap();
+        match records.get(query.name()) {
+            Some(rdatas) => {
+                for rdata in rdatas {
+                    if rdata.record_type() == query.query_type()
+                        || query.query_type() == RecordType::ANY
+                    {
+                        response.add_answer(Record::from_rdata(
+                            query.name().clone(),
+                            60,
+                            rdata.clone(),
+                        ));
+                    }
+                }
+            }
+            None => response.metadata.response_code = ResponseCode::NXDomain,
+        }
+        response.to_vec().ok()
+    }
+}
+
+fn fqdn(name: &str) -> Name {
+    let mut name = Name::from_ascii(name).expect("Invalid domain name");
+    name.set_fqdn(true);
+    name
+}
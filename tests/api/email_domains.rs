@@ -0,0 +1,136 @@
+use crate::dns_stub::DnsStub;
+use crate::helpers::{spawn_app, spawn_app_with, TestApp};
+use newsletter::configuration::DnsCheckSettings;
+use std::net::Ipv4Addr;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+/// Spawns an app checking email domains against the stub nameserver
+async fn spawn_app_with_dns(dns: &DnsStub) -> TestApp {
+    let nameserver = dns.address();
+    spawn_app_with(|c| {
+        c.email_domains.dns = Some(DnsCheckSettings {
+            nameserver: Some(nameserver),
+            timeout_millis: 2000,
+        })
+    })
+    .await
+}
+
+async fn mock_email_server(app: &TestApp, expected: u64) {
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected)
+        .mount(&app.email_server)
+        .await;
+}
+
+#[tokio::test]
+async fn subscribe_accepts_a_domain_with_mx_records() {
+    // init
+    let dns = DnsStub::start().await;
+    dns.add_mx("acme.io", "mail.acme.io");
+    let app = spawn_app_with_dns(&dns).await;
+    mock_email_server(&app, 1).await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40acme.io")
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_falls_back_to_a_records_without_mx_records() {
+    // init
+    let dns = DnsStub::start().await;
+    dns.add_a("acme.io", Ipv4Addr::new(192, 0, 2, 1));
+    let app = spawn_app_with_dns(&dns).await;
+    mock_email_server(&app, 1).await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40acme.io")
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_for_a_domain_that_does_not_exist() {
+    // init
+    let dns = DnsStub::start().await;
+    let app = spawn_app_with_dns(&dns).await;
+    mock_email_server(&app, 0).await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40nowhere.example")
+        .await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["field"], "email");
+    assert_eq!(body["errors"][0]["code"], "email.domain_unknown");
+    assert_eq!(
+        body["errors"][0]["detail"],
+        "The domain nowhere.example does not exist."
+    );
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_for_a_domain_with_a_null_mx() {
+    // init
+    let dns = DnsStub::start().await;
+    dns.add_mx("acme.io", ".");
+    dns.add_a("acme.io", Ipv4Addr::new(192, 0, 2, 1));
+    let app = spawn_app_with_dns(&dns).await;
+    mock_email_server(&app, 0).await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40acme.io")
+        .await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["code"], "email.undeliverable");
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_for_a_disposable_domain() {
+    // init
+    let app =
+        spawn_app_with(|c| c.email_domains.blocklist = vec!["trashmail.de".to_string()]).await;
+    mock_email_server(&app, 0).await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40trashmail.de")
+        .await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["code"], "email.disposable");
+}
+
+#[tokio::test]
+async fn subscribe_skips_the_dns_check_by_default() {
+    // init
+    let app = spawn_app().await;
+    mock_email_server(&app, 1).await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40nowhere.example")
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+}
@@ -0,0 +1,218 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn the_email_log_requires_authentication() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .http_client
+        .get(format!("{}/api/v1/admin/email_log", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn confirmation_emails_are_recorded_in_the_email_log() {
+    // init
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ErrorCode": 0,
+            "Message": "OK",
+            "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817"
+        })))
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    app.post_subscriptions("name=vic%20ji&email=vic_ji_i%40gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let response = app
+        .get_admin_email_log("recipient=vic_ji_i%40gmail.com")
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let entries: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(entries.as_array().unwrap().len(), 1);
+    assert_eq!(entries[0]["kind"], "confirmation");
+    assert_eq!(entries[0]["status"], "accepted");
+    assert_eq!(
+        entries[0]["provider_message_id"],
+        "b7bc2f4a-e38e-4336-af7d-e6c392c2f817"
+    );
+    assert!(entries[0]["issue_id"].is_null());
+}
+
+#[tokio::test]
+async fn the_email_log_filters_recipients_whatever_their_case() {
+    // init
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    app.post_subscriptions("name=vic%20ji&email=Vic_Ji_I%40Gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let entries: serde_json::Value = app
+        .get_admin_email_log("recipient=VIC_JI_I%40gmail.COM")
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(entries.as_array().unwrap().len(), 1);
+    assert_eq!(entries[0]["recipient"], "Vic_Ji_I@gmail.com");
+}
+
+#[tokio::test]
+async fn failed_confirmation_emails_are_recorded_in_the_email_log() {
+    // init
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=vic%20ji&email=vic_ji_i%40gmail.com")
+        .await;
+    assert_eq!(500, response.status().as_u16());
+    let entries: serde_json::Value = app
+        .get_admin_email_log("recipient=vic_ji_i%40gmail.com")
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(entries[0]["kind"], "confirmation");
+    assert_eq!(entries[0]["status"], "transient_failure");
+    assert!(entries[0]["error"].is_string());
+}
+
+#[tokio::test]
+async fn newsletter_deliveries_are_recorded_with_their_issue() {
+    // init
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {"ErrorCode": 0, "Message": "OK", "MessageID": "1b2c3d", "To": "na_me@example.com"}
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let published: serde_json::Value = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>"
+            }
+        }))
+        .await
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let issue_id = published["issue_id"].as_str().unwrap();
+    let entries: serde_json::Value = app
+        .get_admin_email_log(&format!("kind=newsletter&issue_id={}", issue_id))
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(entries.as_array().unwrap().len(), 1);
+    assert_eq!(entries[0]["recipient"], "na_me@example.com");
+    assert_eq!(entries[0]["status"], "accepted");
+    assert_eq!(entries[0]["provider_message_id"], "1b2c3d");
+    assert_eq!(entries[0]["issue_id"], issue_id);
+}
+
+#[tokio::test]
+async fn newsletter_deliveries_rejected_as_a_batch_are_recorded_as_failed() {
+    // init
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+            "ErrorCode": 10,
+            "Message": "The Server Token you provided in the X-Postmark-Server-Token request header was invalid."
+        })))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>"
+            }
+        }))
+        .await;
+    assert_eq!(502, response.status().as_u16());
+    let entries: serde_json::Value = app
+        .get_admin_email_log("kind=newsletter&recipient=na_me%40example.com")
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(entries.as_array().unwrap().len(), 1);
+    assert_eq!(entries[0]["status"], "failed");
+    assert!(entries[0]["error"].as_str().unwrap().contains("code 10"));
+}
+
+#[tokio::test]
+async fn invalid_email_log_filters_are_rejected_with_a_problem_document() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app.get_admin_email_log("issue_id=not-a-uuid").await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "request.malformed");
+}
@@ -1,20 +1,51 @@
-use newsletter::{configuration::DatabaseSettings, HttpServer, Settings};
+use crate::smtp_sink::{ReceivedEmail, SmtpSink};
+use newsletter::{
+    api_keys::{self, Scope},
+    configuration::{BasicAuthSettings, DatabaseSettings, SmtpSettings},
+    HttpServer, Settings,
+};
 use reqwest::Client;
+use secrecy::ExposeSecret;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 
 pub struct TestApp {
     pub address: String,
     pub app_port: u16,
     pub db_pool: PgPool,
     pub email_server: MockServer,
+    pub smtp_sink: SmtpSink,
     pub http_client: Client,
+    pub webhooks: BasicAuthSettings,
+    pub admin: BasicAuthSettings,
+    /// API key granted every scope
+    pub api_key: String,
 }
 
 pub async fn spawn_app() -> TestApp {
-    // start a mock email server
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawns the application after letting the test case adjust its configuration.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
+    spawn(configure, false).await
+}
+
+/// Spawns the app delivering email over SMTP to the app's `smtp_sink`
+pub async fn spawn_smtp_app() -> TestApp {
+    spawn_smtp_app_with(|_| {}).await
+}
+
+pub async fn spawn_smtp_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
+    spawn(configure, true).await
+}
+
+async fn spawn(configure: impl FnOnce(&mut Settings), deliver_over_smtp: bool) -> TestApp {
+    // start a mock email server and an SMTP sink
     let email_server = MockServer::start().await;
+    let smtp_sink = SmtpSink::start().await;
 
     // initialize an HTTP client
     let http_client = Client::builder()
@@ -23,7 +54,7 @@ pub async fn spawn_app() -> TestApp {
         .expect("Failed to build HTTP client");
 
     // randomize configuration to ensure test isolation
-    let conf = {
+    let (conf, (admin, webhooks)) = {
         let mut c = Settings::try_load().expect("Failed to read config");
         // use a random OS port
         c.server.port = 0;
@@ -32,7 +63,20 @@ pub async fn spawn_app() -> TestApp {
 
         // use the mock email server
         c.email_client.base_url = email_server.uri();
-        c
+        if deliver_over_smtp {
+            c.email_client.smtp = Some(SmtpSettings {
+                host: "127.0.0.1".to_string(),
+                port: smtp_sink.port(),
+                starttls: false,
+                username: None,
+                password: None,
+            });
+        }
+
+        // keep the bootstrap owner and webhook credentials, even if the test case disables them
+        let credentials = (c.admin.clone(), c.webhooks.clone());
+        configure(&mut c);
+        (c, credentials)
     };
 
     let db_pool = configure_database(&conf.database).await;
@@ -40,12 +84,23 @@ pub async fn spawn_app() -> TestApp {
     let app = HttpServer::try_new(&conf).await.unwrap();
     let app_port = app.port();
     tokio::spawn(app.run());
+    let (_, api_key) = api_keys::create_api_key(
+        &db_pool,
+        "test",
+        &[Scope::NewslettersPublish, Scope::SubscribersRead],
+    )
+    .await
+    .expect("Failed to create the API key");
     TestApp {
         address: format!("http://localhost:{}", app_port),
         app_port,
         db_pool,
         email_server,
+        smtp_sink,
         http_client,
+        webhooks: webhooks.expect("The dev configuration sets webhook credentials"),
+        admin: admin.expect("The dev configuration sets a bootstrap owner"),
+        api_key,
     }
 }
 
@@ -71,7 +126,7 @@ async fn configure_database(database: &DatabaseSettings) -> PgPool {
 impl TestApp {
     pub async fn post_subscriptions(&self, body: &str) -> reqwest::Response {
         self.http_client
-            .post(format!("{}/subscriptions", self.address))
+            .post(format!("{}/api/v1/subscriptions", self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body.to_string())
             .send()
@@ -79,13 +134,25 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_subscriptions_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/api/v1/subscriptions", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_subscriptions_confirm(&self, token: Option<&str>) -> reqwest::Response {
         let query = match token {
             Some(token) => format!("?subscription_token={}", token),
             None => String::new(),
         };
         self.http_client
-            .get(format!("{}/subscriptions/confirm{}", self.address, query))
+            .get(format!(
+                "{}/api/v1/subscriptions/confirm{}",
+                self.address, query
+            ))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -93,12 +160,165 @@ impl TestApp {
 
     pub async fn post_newsletters(&self, body: &serde_json::Value) -> reqwest::Response {
         self.http_client
-            .post(format!("{}/newsletters", self.address))
+            .post(format!("{}/api/v1/newsletters", self.address))
+            .bearer_auth(&self.api_key)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_postmark_webhook(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/api/v1/webhooks/postmark", self.address))
+            .basic_auth(
+                &self.webhooks.username,
+                Some(self.webhooks.password.expose_secret()),
+            )
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
+
+    pub async fn get_admin_email_log(&self, query: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/api/v1/admin/email_log?{}", self.address, query))
+            .basic_auth(
+                &self.admin.username,
+                Some(self.admin.password.expose_secret()),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_suppressions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/api/v1/admin/suppressions", self.address))
+            .basic_auth(
+                &self.admin.username,
+                Some(self.admin.password.expose_secret()),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_suppressions(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/api/v1/admin/suppressions", self.address))
+            .basic_auth(
+                &self.admin.username,
+                Some(self.admin.password.expose_secret()),
+            )
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers(&self, api_key: &str, query: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/api/v1/subscribers?{}", self.address, query))
+            .bearer_auth(api_key)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_api_keys(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/api/v1/admin/api_keys", self.address))
+            .basic_auth(
+                &self.admin.username,
+                Some(self.admin.password.expose_secret()),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_api_keys(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/api/v1/admin/api_keys", self.address))
+            .basic_auth(
+                &self.admin.username,
+                Some(self.admin.password.expose_secret()),
+            )
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_admin_api_key(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/api/v1/admin/api_keys/{}", self.address, id))
+            .basic_auth(
+                &self.admin.username,
+                Some(self.admin.password.expose_secret()),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Creates an API key granted the given scopes and returns the key
+    pub async fn create_api_key(&self, scopes: &[&str]) -> String {
+        let response = self
+            .post_admin_api_keys(&serde_json::json!({"name": "test", "scopes": scopes}))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+        let body: serde_json::Value = response.json().await.unwrap();
+        body["key"].as_str().unwrap().to_string()
+    }
+
+    pub async fn post_admin_users(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/api/v1/admin/users", self.address))
+            .basic_auth(
+                &self.admin.username,
+                Some(self.admin.password.expose_secret()),
+            )
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Creates an admin user with the given role and returns their credentials
+    pub async fn create_admin_user(&self, role: &str) -> BasicAuthSettings {
+        let username = format!("{}-{}", role, Uuid::new_v4());
+        let password = "a long enough password";
+        let response = self
+            .post_admin_users(&serde_json::json!({
+                "username": username,
+                "password": password,
+                "role": role,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+        BasicAuthSettings {
+            username,
+            password: password.into(),
+        }
+    }
+
+    pub async fn delete_admin_suppression(&self, email: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!(
+                "{}/api/v1/admin/suppressions/{}",
+                self.address, email
+            ))
+            .basic_auth(
+                &self.admin.username,
+                Some(self.admin.password.expose_secret()),
+            )
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
 }
 
 pub struct ConfirmationLinks {
@@ -111,6 +331,23 @@ pub fn get_confirmation_links(email_request: &wiremock::Request, port: u16) -> C
     let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
 
     // extract links from specific fields
+    extract_confirmation_links(
+        body["HtmlBody"].as_str().unwrap(),
+        body["TextBody"].as_str().unwrap(),
+        port,
+    )
+}
+
+/// Extracts the confirmation links of an email delivered to the SMTP sink
+pub fn get_smtp_confirmation_links(email: &ReceivedEmail, port: u16) -> ConfirmationLinks {
+    extract_confirmation_links(
+        &email.body("text/html").unwrap(),
+        &email.body("text/plain").unwrap(),
+        port,
+    )
+}
+
+fn extract_confirmation_links(html: &str, text: &str, port: u16) -> ConfirmationLinks {
     let get_link = |field: &str| {
         let links: Vec<_> = linkify::LinkFinder::new()
             .links(field)
@@ -124,10 +361,43 @@ pub fn get_confirmation_links(email_request: &wiremock::Request, port: u16) -> C
         confirmation_link
     };
 
-    let html_link = get_link(body["HtmlBody"].as_str().unwrap());
-    let text_link = get_link(body["TextBody"].as_str().unwrap());
     ConfirmationLinks {
-        html: html_link,
-        text: text_link,
+        html: get_link(html),
+        text: get_link(text),
     }
 }
+
+/// Use the public API of the program under test to create unconfirmed subscribers.
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let body = "name=na%20me&email=na_me%40example.com";
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Check the request received by the mock Postmark server to obtain the confirmation link and return it.
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    get_confirmation_links(&email_request, app.app_port)
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let confirmation_links = create_unconfirmed_subscriber(app).await;
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
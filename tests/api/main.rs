@@ -1,5 +1,19 @@
+mod api_keys;
+mod bot_protection;
+mod cors;
+mod dns_stub;
+mod email_domains;
+mod email_log;
 mod health_check;
 mod helpers;
 mod newsletter;
+mod openapi;
+mod smtp;
+mod smtp_sink;
+mod subscribe_form;
 mod subscriptions;
 mod subscriptions_confirm;
+mod suppressions;
+mod users;
+mod versioning;
+mod webhooks;
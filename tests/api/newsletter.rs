@@ -1,4 +1,8 @@
-use crate::helpers::{get_confirmation_links, spawn_app, ConfirmationLinks, TestApp};
+use crate::helpers::{
+    create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app, spawn_app_with,
+};
+use newsletter::configuration::{DkimAlgorithm, DkimSettings};
+use newsletter::{HttpServer, Settings};
 use wiremock::matchers::{any, method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -31,50 +35,17 @@ async fn newsletter_are_not_delivered_to_unconfirmed_subscribers() {
     assert_eq!(response.status().as_u16(), 200);
 }
 
-/// Use the public API of the program under test to create unconfirmed subscribers.
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let body = "name=na%20me&email=na_me%40example.com";
-
-    let _mock_guard = Mock::given(path("/email"))
-        .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200))
-        .expect(1)
-        .mount_as_scoped(&app.email_server)
-        .await;
-    app.post_subscriptions(body)
-        .await
-        .error_for_status()
-        .unwrap();
-
-    // Check the request received by the mock Postmark server to obtain the confirmation link and return it.
-    let email_request = app
-        .email_server
-        .received_requests()
-        .await
-        .unwrap()
-        .pop()
-        .unwrap();
-    get_confirmation_links(&email_request, app.app_port)
-}
-
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_links = create_unconfirmed_subscriber(app).await;
-    reqwest::get(confirmation_links.html)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
-}
-
 #[tokio::test]
 async fn newsletters_are_delivered_to_confirmed_subscribers() {
     // Prepare
     let app = spawn_app().await;
     create_confirmed_subscriber(&app).await;
 
-    Mock::given(path("/email"))
+    Mock::given(path("/email/batch"))
         .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {"ErrorCode": 0, "Message": "OK", "To": "na_me@example.com"}
+        ])))
         .expect(1)
         .mount(&app.email_server)
         .await;
@@ -123,5 +94,220 @@ async fn newsletters_returns_400_for_invalid_data() {
             "The API did not fail with 422 Unprocessable Entity when the payload was {}.",
             error_message
         );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["code"], "request.invalid");
+        assert_eq!(body["errors"][0]["code"], "field.missing");
     }
 }
+
+#[tokio::test]
+async fn recipients_rejected_as_inactive_are_suppressed() {
+    // Prepare
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {
+                "ErrorCode": 406,
+                "Message": "You tried to send to a recipient that has been marked as inactive.",
+                "To": "na_me@example.com"
+            }
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Execute
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>"
+        }
+    });
+    let response = app.post_newsletters(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = app.get_admin_suppressions().await.json().await.unwrap();
+    assert_eq!(body[0]["email"], "na_me@example.com");
+    assert_eq!(body[0]["reason"], "inactive_recipient");
+}
+
+#[tokio::test]
+async fn newsletters_returns_502_when_no_message_was_accepted() {
+    // Prepare
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Execute
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>"
+        }
+    });
+    let response = app.post_newsletters(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 502);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "newsletter.not_delivered");
+}
+
+/// Returns the first message of the first batch received by the mock email server
+async fn first_batch_message(app: &crate::helpers::TestApp) -> serde_json::Value {
+    let requests = app.email_server.received_requests().await.unwrap();
+    let request = requests
+        .iter()
+        .find(|r| r.url.path() == "/email/batch")
+        .expect("No batch was sent");
+    let batch: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    batch[0].clone()
+}
+
+#[tokio::test]
+async fn newsletters_are_sent_from_the_configured_mailbox() {
+    // Prepare
+    let app = spawn_app_with(|c| {
+        c.email_client.sender_email = r#""Acme News" <news@acme.io>"#.to_string();
+        c.email_client.reply_to = Some("support@acme.io".to_string());
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {"ErrorCode": 0, "Message": "OK", "To": "na_me@example.com"}
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Execute
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>"
+        }
+    });
+    let response = app.post_newsletters(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let message = first_batch_message(&app).await;
+    assert_eq!(message["From"], r#""Acme News" <news@acme.io>"#);
+    assert_eq!(message["ReplyTo"], "support@acme.io");
+}
+
+#[tokio::test]
+async fn newsletters_can_override_the_sender_per_issue() {
+    // Prepare
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email/batch"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {"ErrorCode": 0, "Message": "OK", "To": "na_me@example.com"}
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Execute
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>"
+        },
+        "from": "Release Notes <releases@acme.io>",
+        "reply_to": "\"Acme Team\" <team@acme.io>"
+    });
+    let response = app.post_newsletters(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let message = first_batch_message(&app).await;
+    assert_eq!(message["From"], r#""Release Notes" <releases@acme.io>"#);
+    assert_eq!(message["ReplyTo"], r#""Acme Team" <team@acme.io>"#);
+}
+
+#[tokio::test]
+async fn newsletters_returns_400_for_an_invalid_sender_override() {
+    // Prepare
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Execute
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>"
+        },
+        "from": "Release Notes <releases.acme.io>"
+    });
+    let response = app.post_newsletters(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn the_server_refuses_to_start_with_an_invalid_sender() {
+    // Prepare
+    let mut conf = Settings::try_load().expect("Failed to read config");
+    conf.server.port = 0;
+    conf.email_client.sender_email = "Acme News <not-an-address>".to_string();
+
+    // Execute
+    let result = HttpServer::try_new(&conf).await;
+
+    // Assert
+    let error = result.err().expect("The server should not start");
+    assert!(format!("{:?}", error).contains("sender_email"));
+}
+
+#[tokio::test]
+async fn the_server_refuses_to_start_with_a_sender_off_the_dkim_domain() {
+    // Prepare
+    let mut conf = Settings::try_load().expect("Failed to read config");
+    conf.server.port = 0;
+    conf.email_client.sender_email = "Acme News <news@other.org>".to_string();
+    conf.email_client.dkim = Some(DkimSettings {
+        selector: "newsletter".to_string(),
+        domain: "acme.io".to_string(),
+        algorithm: DkimAlgorithm::Rsa,
+        private_key: None,
+        private_key_path: Some("tests/fixtures/dkim_rsa.pem".to_string()),
+    });
+
+    // Execute
+    let result = HttpServer::try_new(&conf).await;
+
+    // Assert
+    let error = result.err().expect("The server should not start");
+    assert!(format!("{:?}", error).contains("not on the DKIM signing domain"));
+}
@@ -0,0 +1,74 @@
+use crate::helpers::{spawn_app, spawn_app_with};
+use std::path::PathBuf;
+
+/// Checked-in OpenAPI document, regenerate it with `UPDATE_SNAPSHOTS=1 cargo test openapi`
+fn snapshot_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/api/snapshots/openapi.json")
+}
+
+#[tokio::test]
+async fn the_served_openapi_document_matches_the_snapshot() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .http_client
+        .get(format!("{}/openapi.json", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let served: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(served["openapi"], "3.1.0");
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        let document = serde_json::to_string_pretty(&served).unwrap() + "\n";
+        std::fs::write(snapshot_path(), document).expect("Failed to write the snapshot");
+    }
+    let snapshot: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(snapshot_path()).expect("Failed to read the snapshot"),
+    )
+    .unwrap();
+    assert_eq!(
+        served, snapshot,
+        "The OpenAPI document drifted from tests/api/snapshots/openapi.json, \
+        run `UPDATE_SNAPSHOTS=1 cargo test openapi` and review the changes"
+    );
+}
+
+#[tokio::test]
+async fn the_documentation_ui_is_served_when_enabled() {
+    // init
+    let app = spawn_app_with(|c| c.api_docs.ui = true).await;
+
+    // execute
+    let response = app
+        .http_client
+        .get(format!("{}/docs", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("/openapi.json"));
+}
+
+#[tokio::test]
+async fn the_documentation_ui_is_not_served_when_disabled() {
+    // init
+    let app = spawn_app_with(|c| c.api_docs.ui = false).await;
+
+    // execute
+    let response = app
+        .http_client
+        .get(format!("{}/docs", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,224 @@
+use crate::helpers::{get_smtp_confirmation_links, spawn_smtp_app, spawn_smtp_app_with, TestApp};
+use newsletter::configuration::{DkimAlgorithm, DkimSettings};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+/// Subscribes and confirms na_me@example.com using the link delivered to the SMTP sink
+async fn create_confirmed_subscriber(app: &TestApp) {
+    app.post_subscriptions("name=na%20me&email=na_me%40example.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let email = app.smtp_sink.received_emails().pop().unwrap();
+    let confirmation_links = get_smtp_confirmation_links(&email, app.app_port);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>"
+        }
+    })
+}
+
+#[tokio::test]
+async fn confirmation_emails_are_delivered_over_smtp() {
+    // Prepare
+    let app = spawn_smtp_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Execute
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let emails = app.smtp_sink.received_emails();
+    assert_eq!(emails.len(), 1);
+    let email = &emails[0];
+    assert_eq!(email.recipients, vec!["ursula_le_guin@gmail.com"]);
+    assert_eq!(email.header("Subject").unwrap(), "Welcome!");
+    assert_eq!(
+        email.mimetypes(),
+        vec!["multipart/alternative", "text/plain", "text/html"]
+    );
+}
+
+#[tokio::test]
+async fn the_link_delivered_over_smtp_confirms_a_subscriber() {
+    // Prepare
+    let app = spawn_smtp_app().await;
+
+    // Execute
+    create_confirmed_subscriber(&app).await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn newsletters_are_delivered_over_smtp_from_the_configured_mailbox() {
+    // Prepare
+    let app = spawn_smtp_app_with(|c| {
+        c.email_client.sender_email = r#""Acme News" <news@acme.io>"#.to_string();
+        c.email_client.reply_to = Some("support@acme.io".to_string());
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+
+    // Execute
+    let response = app.post_newsletters(&newsletter_request_body()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let email = app.smtp_sink.received_emails().pop().unwrap();
+    assert_eq!(email.envelope_from, "news@acme.io");
+    assert_eq!(email.recipients, vec!["na_me@example.com"]);
+    assert_eq!(
+        email.header("From").unwrap(),
+        "\"Acme News\" <news@acme.io>"
+    );
+    assert_eq!(email.header("Reply-To").unwrap(), "support@acme.io");
+    assert_eq!(email.header("Subject").unwrap(), "Newsletter title");
+    assert_eq!(
+        email.body("text/plain").unwrap().trim_end(),
+        "Newsletter body as plain text"
+    );
+    assert_eq!(
+        email.body("text/html").unwrap().trim_end(),
+        "<p>Newsletter body as HTML</p>"
+    );
+}
+
+#[tokio::test]
+async fn recipients_rejected_by_the_smtp_relay_are_suppressed() {
+    // Prepare
+    let app = spawn_smtp_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.smtp_sink.reject_recipient("na_me@example.com");
+
+    // Execute
+    let response = app.post_newsletters(&newsletter_request_body()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = app.get_admin_suppressions().await.json().await.unwrap();
+    assert_eq!(body[0]["email"], "na_me@example.com");
+    assert_eq!(body[0]["reason"], "inactive_recipient");
+}
+
+fn dkim_settings() -> DkimSettings {
+    DkimSettings {
+        selector: "newsletter".to_string(),
+        domain: "acme.io".to_string(),
+        algorithm: DkimAlgorithm::Rsa,
+        private_key: None,
+        private_key_path: Some("tests/fixtures/dkim_rsa.pem".to_string()),
+    }
+}
+
+#[tokio::test]
+async fn messages_are_dkim_signed_when_configured() {
+    // Prepare
+    let app = spawn_smtp_app_with(|c| {
+        c.email_client.sender_email = r#""Acme News" <news@acme.io>"#.to_string();
+        c.email_client.dkim = Some(dkim_settings());
+    })
+    .await;
+
+    // Execute
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let email = app.smtp_sink.received_emails().pop().unwrap();
+    let signature = email.header("DKIM-Signature").unwrap();
+    assert!(signature.contains("d=acme.io"));
+    assert!(signature.contains("s=newsletter"));
+    let signed_headers = signature
+        .split(';')
+        .find_map(|tag| tag.trim().strip_prefix("h="))
+        .unwrap()
+        .to_lowercase();
+    assert!(signed_headers.contains("reply-to"));
+    assert!(signed_headers.contains("cc"));
+}
+
+#[tokio::test]
+async fn newsletters_returns_400_for_a_sender_off_the_dkim_domain() {
+    // Prepare
+    let app = spawn_smtp_app_with(|c| {
+        c.email_client.sender_email = r#""Acme News" <news@acme.io>"#.to_string();
+        c.email_client.dkim = Some(dkim_settings());
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    let received = app.smtp_sink.received_emails().len();
+
+    // Execute
+    let mut newsletter_request_body = newsletter_request_body();
+    newsletter_request_body["from"] = "Release Notes <releases@other.org>".into();
+    let response = app.post_newsletters(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["field"], "from");
+    assert_eq!(body["errors"][0]["code"], "mailbox.invalid");
+    assert_eq!(app.smtp_sink.received_emails().len(), received);
+}
+
+#[tokio::test]
+async fn newsletters_accept_a_sender_on_a_dkim_subdomain() {
+    // Prepare
+    let app = spawn_smtp_app_with(|c| {
+        c.email_client.sender_email = r#""Acme News" <news@acme.io>"#.to_string();
+        c.email_client.dkim = Some(dkim_settings());
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+
+    // Execute
+    let mut newsletter_request_body = newsletter_request_body();
+    newsletter_request_body["from"] = "Release Notes <releases@news.acme.io>".into();
+    let response = app.post_newsletters(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let email = app.smtp_sink.received_emails().pop().unwrap();
+    assert_eq!(email.envelope_from, "releases@news.acme.io");
+}
+
+#[tokio::test]
+async fn messages_are_not_signed_by_default() {
+    // Prepare
+    let app = spawn_smtp_app().await;
+
+    // Execute
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let email = app.smtp_sink.received_emails().pop().unwrap();
+    assert!(email.header("DKIM-Signature").is_none());
+}
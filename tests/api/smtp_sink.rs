@@ -0,0 +1,181 @@
+use mailparse::{MailHeaderMap, ParsedMail};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// An in-process SMTP server recording every message it accepts
+///
+/// It speaks just enough SMTP for a relay client: no TLS and no authentication.
+#[derive(Clone)]
+pub struct SmtpSink {
+    port: u16,
+    received: Arc<Mutex<Vec<ReceivedEmail>>>,
+    rejected: Arc<Mutex<HashSet<String>>>,
+}
+
+/// A message accepted by the sink
+#[derive(Clone, Debug)]
+pub struct ReceivedEmail {
+    pub envelope_from: String,
+    pub recipients: Vec<String>,
+    pub raw: Vec<u8>,
+}
+
+/// One SMTP transaction in progress
+#[derive(Default)]
+struct Transaction {
+    envelope_from: Option<String>,
+    recipients: Vec<String>,
+}
+
+impl SmtpSink {
+    /// Starts the sink on a random local port
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind the SMTP sink");
+        let sink = Self {
+            port: listener.local_addr().unwrap().port(),
+            received: Arc::default(),
+            rejected: Arc::default(),
+        };
+
+        let server = sink.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(server.clone().handle(stream));
+            }
+        });
+        sink
+    }
+
+    /// Returns the port the sink is listening on
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Answers `550` to every `RCPT TO` naming this address
+    pub fn reject_recipient(&self, address: &str) {
+        self.rejected.lock().unwrap().insert(address.to_string());
+    }
+
+    /// Returns the messages accepted so far, oldest first
+    pub fn received_emails(&self) -> Vec<ReceivedEmail> {
+        self.received.lock().unwrap().clone()
+    }
+
+    async fn handle(self, stream: TcpStream) {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut transaction = Transaction::default();
+
+        let _ = writer.write_all(b"220 localhost SMTP sink\r\n").await;
+        while let Ok(Some(line)) = lines.next_line().await {
+            let command = line.to_ascii_uppercase();
+            let reply = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                "250-localhost\r\n250 8BITMIME".to_string()
+            } else if command.starts_with("MAIL FROM:") {
+                transaction = Transaction {
+                    envelope_from: Some(address_of(&line)),
+                    recipients: Vec::new(),
+                };
+                "250 OK".to_string()
+            } else if command.starts_with("RCPT TO:") {
+                let recipient = address_of(&line);
+                if self.rejected.lock().unwrap().contains(&recipient) {
+                    "550 5.1.1 Mailbox unavailable".to_string()
+                } else {
+                    transaction.recipients.push(recipient);
+                    "250 OK".to_string()
+                }
+            } else if command == "DATA" {
+                let _ = writer
+                    .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                    .await;
+                let mut raw = Vec::new();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line == "." {
+                        break;
+                    }
+                    // Undo the dot-stuffing of lines starting with a dot
+                    let line = line.strip_prefix('.').unwrap_or(&line);
+                    raw.extend_from_slice(line.as_bytes());
+                    raw.extend_from_slice(b"\r\n");
+                }
+                let transaction = std::mem::take(&mut transaction);
+                self.received.lock().unwrap().push(ReceivedEmail {
+                    envelope_from: transaction.envelope_from.unwrap_or_default(),
+                    recipients: transaction.recipients,
+                    raw,
+                });
+                "250 OK queued".to_string()
+            } else if command == "RSET" {
+                transaction = Transaction::default();
+                "250 OK".to_string()
+            } else if command == "NOOP" {
+                "250 OK".to_string()
+            } else if command == "QUIT" {
+                let _ = writer.write_all(b"221 Bye\r\n").await;
+                break;
+            } else {
+                "502 Command not implemented".to_string()
+            };
+            if writer
+                .write_all(format!("{}\r\n", reply).as_bytes())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+/// Extracts the address of a `MAIL FROM:<...>` or `RCPT TO:<...>` command
+fn address_of(command: &str) -> String {
+    command
+        .split_once('<')
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .map(|(address, _)| address.to_string())
+        .unwrap_or_default()
+}
+
+impl ReceivedEmail {
+    /// Parses the MIME structure of the message
+    pub fn parsed(&self) -> ParsedMail<'_> {
+        mailparse::parse_mail(&self.raw).expect("The SMTP sink received invalid MIME")
+    }
+
+    /// Returns the decoded value of a top level header
+    pub fn header(&self, name: &str) -> Option<String> {
+        self.parsed().get_headers().get_first_value(name)
+    }
+
+    /// Returns the decoded body of the first part with the given MIME type
+    pub fn body(&self, mimetype: &str) -> Option<String> {
+        let parsed = self.parsed();
+        find_part(&parsed, mimetype).map(|part| part.get_body().unwrap())
+    }
+
+    /// Returns the MIME types of every part, depth first
+    pub fn mimetypes(&self) -> Vec<String> {
+        let mut mimetypes = Vec::new();
+        collect_mimetypes(&self.parsed(), &mut mimetypes);
+        mimetypes
+    }
+}
+
+fn find_part<'a>(part: &'a ParsedMail<'a>, mimetype: &str) -> Option<&'a ParsedMail<'a>> {
+    if part.ctype.mimetype == mimetype {
+        return Some(part);
+    }
+    part.subparts.iter().find_map(|p| find_part(p, mimetype))
+}
+
+fn collect_mimetypes(part: &ParsedMail<'_>, mimetypes: &mut Vec<String>) {
+    mimetypes.push(part.ctype.mimetype.clone());
+    for subpart in &part.subparts {
+        collect_mimetypes(subpart, mimetypes);
+    }
+}
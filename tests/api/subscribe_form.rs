@@ -0,0 +1,208 @@
+use crate::helpers::{spawn_app, TestApp};
+use reqwest::{header, redirect::Policy, Client, Response};
+use std::collections::HashMap;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Client keeping the cookies set by the app, without following redirects
+struct Browser {
+    client: Client,
+    cookies: HashMap<String, String>,
+}
+
+impl Browser {
+    fn new() -> Self {
+        Self {
+            client: Client::builder().redirect(Policy::none()).build().unwrap(),
+            cookies: HashMap::new(),
+        }
+    }
+
+    async fn get(&mut self, url: &str) -> Response {
+        let request = self.client.get(url);
+        let response = self.with_cookies(request).send().await.unwrap();
+        self.store_cookies(&response);
+        response
+    }
+
+    async fn post_form(&mut self, url: &str, form: &[(&str, &str)]) -> Response {
+        let request = self.client.post(url).form(form);
+        let response = self.with_cookies(request).send().await.unwrap();
+        self.store_cookies(&response);
+        response
+    }
+
+    fn with_cookies(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let cookies: Vec<String> = self
+            .cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        request.header(header::COOKIE, cookies.join("; "))
+    }
+
+    fn store_cookies(&mut self, response: &Response) {
+        for set_cookie in response.headers().get_all(header::SET_COOKIE) {
+            let set_cookie = set_cookie.to_str().unwrap();
+            let (pair, attributes) = set_cookie.split_once(';').unwrap_or((set_cookie, ""));
+            let (name, value) = pair.split_once('=').unwrap();
+            if attributes.contains("Max-Age=0") {
+                self.cookies.remove(name);
+            } else {
+                self.cookies.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+}
+
+/// Reads the value of a hidden input of the form
+fn hidden_input(page: &str, name: &str) -> String {
+    let marker = format!("name=\"{}\" value=\"", name);
+    let (_, rest) = page.split_once(&marker).unwrap();
+    rest.split_once('"').unwrap().0.to_string()
+}
+
+/// Opens the form and returns the CSRF token it embeds
+async fn open_form(app: &TestApp, browser: &mut Browser) -> String {
+    let page = browser
+        .get(&format!("{}/subscribe", app.address))
+        .await
+        .text()
+        .await
+        .unwrap();
+    hidden_input(&page, "csrf_token")
+}
+
+#[tokio::test]
+async fn the_subscription_form_is_served_as_html() {
+    // init
+    let app = spawn_app().await;
+    let mut browser = Browser::new();
+
+    // execute
+    let response = browser.get(&format!("{}/subscribe", app.address)).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert_eq!(response.headers()["Cache-Control"], "no-store");
+    assert!(browser.cookies.contains_key("newsletter_csrf"));
+    let page = response.text().await.unwrap();
+    assert!(page.contains(r#"action="/api/v1/subscriptions""#));
+    assert!(!hidden_input(&page, "csrf_token").is_empty());
+}
+
+#[tokio::test]
+async fn submitting_the_form_subscribes_and_shows_a_success_message() {
+    // init
+    let app = spawn_app().await;
+    let mut browser = Browser::new();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let csrf_token = open_form(&app, &mut browser).await;
+
+    // execute
+    let response = browser
+        .post_form(
+            &format!("{}/api/v1/subscriptions", app.address),
+            &[
+                ("name", "Ada Lovelace"),
+                ("email", "ada@example.com"),
+                ("csrf_token", &csrf_token),
+            ],
+        )
+        .await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers()["Location"], "/subscribe");
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.email, "ada@example.com");
+
+    let page = browser
+        .get(&format!("{}/subscribe", app.address))
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("flash-success"));
+    assert!(page.contains("Thanks for subscribing!"));
+    // The message is only shown once
+    assert!(!browser.cookies.contains_key("newsletter_flash"));
+}
+
+#[tokio::test]
+async fn invalid_submissions_are_sent_back_to_the_form_with_the_error() {
+    // init
+    let app = spawn_app().await;
+    let mut browser = Browser::new();
+    let csrf_token = open_form(&app, &mut browser).await;
+
+    // execute
+    let response = browser
+        .post_form(
+            &format!("{}/api/v1/subscriptions", app.address),
+            &[
+                ("name", "Ada <Lovelace>"),
+                ("email", "ada@example.com"),
+                ("csrf_token", &csrf_token),
+            ],
+        )
+        .await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 303);
+    let page = browser
+        .get(&format!("{}/subscribe", app.address))
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("flash-error"));
+    assert!(page.contains("forbidden character"));
+}
+
+#[tokio::test]
+async fn submissions_without_the_csrf_cookie_are_rejected() {
+    // init
+    let app = spawn_app().await;
+    let csrf_token = open_form(&app, &mut Browser::new()).await;
+    let mut other_browser = Browser::new();
+
+    // execute
+    let response = other_browser
+        .post_form(
+            &format!("{}/api/v1/subscriptions", app.address),
+            &[
+                ("name", "Ada Lovelace"),
+                ("email", "ada@example.com"),
+                ("csrf_token", &csrf_token),
+            ],
+        )
+        .await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 303);
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert!(saved.is_none());
+    let page = other_browser
+        .get(&format!("{}/subscribe", app.address))
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("The form has expired"));
+}
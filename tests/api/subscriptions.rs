@@ -1,4 +1,7 @@
-use crate::helpers::{get_confirmation_links, spawn_app};
+use crate::helpers::{
+    create_confirmed_subscriber, get_confirmation_links, spawn_app, spawn_app_with,
+};
+use std::num::NonZeroU32;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -69,6 +72,10 @@ async fn subscribe_returns_422_when_data_is_missing() {
             "The API did not fail with 422 Unprocessable Entity when the payload was {}.",
             error_message
         );
+        assert_eq!(
+            response.headers()["Content-Type"],
+            "application/problem+json"
+        );
     }
 }
 
@@ -141,3 +148,328 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
     // two links should be the same.
     assert_eq!(confirmation_links.html, confirmation_links.text);
 }
+
+#[tokio::test]
+async fn subscribe_returns_429_when_a_client_sends_too_many_requests() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    // invalid payloads still count towards the per-IP quota
+    for _ in 0..10 {
+        let response = app.post_subscriptions("name=&email=").await;
+        assert_eq!(400, response.status().as_u16());
+    }
+    let response = app.post_subscriptions("name=&email=").await;
+
+    // assert
+    assert_eq!(429, response.status().as_u16());
+    assert!(response.headers().contains_key("Retry-After"));
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], 429);
+    assert_eq!(body["code"], "rate_limit.exceeded");
+}
+
+#[tokio::test]
+async fn fetching_form_tokens_does_not_count_towards_the_per_ip_quota() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    for _ in 0..11 {
+        let response = app
+            .http_client
+            .get(format!("{}/api/v1/subscriptions/form_token", app.address))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(200, response.status().as_u16());
+    }
+    let response = app.post_subscriptions("name=&email=").await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_returns_429_when_an_email_is_submitted_too_often() {
+    // init
+    let app = spawn_app_with(|c| {
+        c.server.rate_limit.subscriptions_per_email_per_hour = NonZeroU32::new(1).unwrap();
+    })
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=vic%20ji&email=vic_ji_i%40gmail.com")
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    let response = app
+        .post_subscriptions("name=vic%20ji&email=VIC_ji_i%40Gmail.com")
+        .await;
+
+    // assert
+    assert_eq!(429, response.status().as_u16());
+    assert!(response.headers().contains_key("Retry-After"));
+}
+
+#[tokio::test]
+async fn subscribe_stores_the_canonical_email_address() {
+    // init
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=vic%20ji&email=%20Vic_Ji%40B%C3%BCcher.Example%20")
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT email, email_normalized FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.email, "Vic_Ji@xn--bcher-kva.example");
+    assert_eq!(saved.email_normalized, "vic_ji@xn--bcher-kva.example");
+}
+
+#[tokio::test]
+async fn subscribe_does_not_create_a_second_subscriber_for_a_case_variant() {
+    // init
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let first = app
+        .post_subscriptions("name=vic%20ji&email=vic_ji%40gmail.com")
+        .await;
+    let second = app
+        .post_subscriptions("name=vic%20ji&email=Vic_Ji%40GMAIL.com")
+        .await;
+
+    // assert
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(200, second.status().as_u16());
+    let saved = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count saved subscriptions.");
+    assert_eq!(saved.count, 1);
+}
+
+#[tokio::test]
+async fn subscribing_again_once_confirmed_sends_no_email() {
+    // init
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=na%20me&email=NA_ME%40Example.com")
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn subscribe_stores_the_normalized_name() {
+    // init
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=%20Ame%CC%81lie%20%20%20Poulain%20&email=vic_ji%40gmail.com")
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.name, "Amélie Poulain");
+}
+
+#[tokio::test]
+async fn subscribe_tells_where_a_forbidden_character_is_in_the_name() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=vic%E2%80%AEji&email=vic_ji%40gmail.com")
+        .await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["field"], "name");
+    assert_eq!(body["errors"][0]["code"], "name.forbidden_character");
+    assert_eq!(
+        body["errors"][0]["detail"],
+        "The name contains a forbidden character U+202E at position 4."
+    );
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_problem_document_listing_every_invalid_field() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app.post_subscriptions("name=%20&email=not-an-email").await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["type"], "about:blank");
+    assert_eq!(body["title"], "Bad Request");
+    assert_eq!(body["status"], 400);
+    assert_eq!(body["code"], "request.invalid");
+    let codes: Vec<(&str, &str)> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["field"].as_str().unwrap(), e["code"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        codes,
+        vec![("email", "email.invalid"), ("name", "name.empty")]
+    );
+}
+
+#[tokio::test]
+async fn subscribe_accepts_json_bodies() {
+    // init
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({
+            "name": "vic ji",
+            "email": "vic_ji_i@gmail.com"
+        }))
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.email, "vic_ji_i@gmail.com");
+    assert_eq!(saved.name, "vic ji");
+}
+
+#[tokio::test]
+async fn subscribe_returns_422_naming_the_field_missing_from_a_json_body() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .post_subscriptions_json(&serde_json::json!({ "name": "vic ji" }))
+        .await;
+
+    // assert
+    assert_eq!(422, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "request.invalid");
+    assert_eq!(body["errors"][0]["field"], "email");
+    assert_eq!(body["errors"][0]["code"], "field.missing");
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_for_malformed_json() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .http_client
+        .post(format!("{}/api/v1/subscriptions", app.address))
+        .header("Content-Type", "application/json")
+        .body(r#"{"name": "vic ji", "#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "request.malformed");
+}
+
+#[tokio::test]
+async fn subscribe_returns_415_for_other_content_types() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .http_client
+        .post(format!("{}/api/v1/subscriptions", app.address))
+        .header("Content-Type", "text/plain")
+        .body("name=vic%20ji&email=vic_ji_i%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(415, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "request.unsupported_media_type");
+}
@@ -1,4 +1,6 @@
-use crate::helpers::{get_confirmation_links, spawn_app};
+use crate::helpers::{
+    create_unconfirmed_subscriber, get_confirmation_links, spawn_app, spawn_app_with,
+};
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
@@ -14,6 +16,14 @@ async fn confirmations_without_token_are_rejected_with_a_400() {
 
     // assert
     assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "request.malformed");
+    assert_eq!(body["errors"][0]["field"], "subscription_token");
+    assert_eq!(body["errors"][0]["code"], "field.missing");
 }
 
 #[tokio::test]
@@ -87,4 +97,184 @@ async fn invalid_token_returns_401() {
     let response = app.get_subscriptions_confirm(Some("invalid_token")).await;
     // assert
     assert_eq!(response.status().as_u16(), 401);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "token.unknown");
+}
+
+#[tokio::test]
+async fn api_clients_are_told_whether_the_subscriber_was_already_confirmed() {
+    // init
+    let app = spawn_app().await;
+    let confirmation_link = create_unconfirmed_subscriber(&app).await;
+
+    // execute
+    let first = reqwest::get(confirmation_link.html.clone()).await.unwrap();
+    let second = reqwest::get(confirmation_link.html).await.unwrap();
+
+    // assert
+    assert_eq!(first.status().as_u16(), 200);
+    let body: serde_json::Value = first.json().await.unwrap();
+    assert_eq!(body["status"], "confirmed");
+    assert_eq!(second.status().as_u16(), 200);
+    let body: serde_json::Value = second.json().await.unwrap();
+    assert_eq!(body["status"], "already_confirmed");
+}
+
+#[tokio::test]
+async fn expired_tokens_are_rejected_with_a_410() {
+    // init
+    let app = spawn_app_with(|c| c.confirmation.token_ttl_hours = 0).await;
+    let confirmation_link = create_unconfirmed_subscriber(&app).await;
+
+    // execute
+    let response = reqwest::get(confirmation_link.html).await.unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 410);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "token.expired");
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn a_bounced_subscriber_following_their_link_stays_bounced() {
+    // init
+    let app = spawn_app().await;
+    let confirmation_link = create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'bounced'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // execute
+    let response = reqwest::get(confirmation_link.html).await.unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 401);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "token.unknown");
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.status, "bounced");
+}
+
+#[tokio::test]
+async fn subscribing_again_after_a_link_expired_sends_a_new_link() {
+    // init
+    let app = spawn_app().await;
+    let expired_link = create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscription_tokens SET created_at = now() - interval '30 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let expired = reqwest::get(expired_link.html).await.unwrap();
+    assert_eq!(expired.status().as_u16(), 410);
+
+    // execute
+    let new_link = create_unconfirmed_subscriber(&app).await;
+    let response = reqwest::get(new_link.html).await.unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "confirmed");
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn browsers_get_an_html_landing_page() {
+    // init
+    let app = spawn_app().await;
+    let confirmation_link = create_unconfirmed_subscriber(&app).await;
+    let get_page = |url: reqwest::Url| {
+        app.http_client
+            .get(url)
+            .header("Accept", "text/html,application/xhtml+xml")
+            .send()
+    };
+
+    // execute
+    let confirmed = get_page(confirmation_link.html.clone()).await.unwrap();
+    let already_confirmed = get_page(confirmation_link.html.clone()).await.unwrap();
+    let mut unknown_link = confirmation_link.html;
+    unknown_link.set_query(Some("subscription_token=unknown"));
+    let unknown = get_page(unknown_link).await.unwrap();
+
+    // assert
+    for (response, status, heading) in [
+        (confirmed, 200, "You're in!"),
+        (already_confirmed, 200, "Already confirmed"),
+        (unknown, 401, "This link is not valid"),
+    ] {
+        assert_eq!(response.status().as_u16(), status);
+        assert!(response.headers()["Content-Type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+        let page = response.text().await.unwrap();
+        assert!(page.contains(heading), "Missing {:?} in {}", heading, page);
+    }
+}
+
+#[tokio::test]
+async fn browsers_following_an_expired_link_get_an_html_page() {
+    // init
+    let app = spawn_app_with(|c| c.confirmation.token_ttl_hours = 0).await;
+    let confirmation_link = create_unconfirmed_subscriber(&app).await;
+
+    // execute
+    let response = app
+        .http_client
+        .get(confirmation_link.html)
+        .header("Accept", "text/html")
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 410);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("This link has expired"));
+}
+
+#[tokio::test]
+async fn browsers_are_redirected_to_the_configured_pages() {
+    // init
+    let app = spawn_app_with(|c| {
+        c.confirmation.redirects.confirmed = Some("https://example.com/welcome".to_string())
+    })
+    .await;
+    let confirmation_link = create_unconfirmed_subscriber(&app).await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    // execute
+    let response = client
+        .get(confirmation_link.html)
+        .header("Accept", "text/html")
+        .send()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(
+        response.headers()["Location"],
+        "https://example.com/welcome"
+    );
 }
@@ -0,0 +1,205 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn admin_suppressions_require_authentication() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .http_client
+        .post(format!("{}/api/v1/admin/suppressions", app.address))
+        .json(&serde_json::json!({"email": "na_me@example.com", "reason": "legal"}))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="admin""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn suppressed_addresses_are_listed() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    app.post_admin_suppressions(&serde_json::json!({
+        "email": "na_me@example.com",
+        "reason": "legal request",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    let response = app.get_admin_suppressions().await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body[0]["email"], "na_me@example.com");
+    assert_eq!(body[0]["reason"], "legal request");
+    assert_eq!(body[0]["source"], "admin");
+}
+
+#[tokio::test]
+async fn adding_a_suppression_returns_400_for_invalid_data() {
+    // init
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"email": "not-an-email", "reason": "legal"}),
+            "invalid email",
+        ),
+        (
+            serde_json::json!({"email": "na_me@example.com", "reason": " "}),
+            "empty reason",
+        ),
+    ];
+
+    for (invalid_body, error_message) in test_cases {
+        // execute
+        let response = app.post_admin_suppressions(&invalid_body).await;
+
+        // assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not return 400 Bad Request when the payload was {}.",
+            error_message
+        );
+    }
+}
+
+#[tokio::test]
+async fn removing_an_unknown_suppression_returns_404() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app.delete_admin_suppression("na_me@example.com").await;
+
+    // assert
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn suppressed_addresses_do_not_receive_a_confirmation_email() {
+    // init
+    let app = spawn_app().await;
+    app.post_admin_suppressions(&serde_json::json!({
+        "email": "na_me@example.com",
+        "reason": "legal request",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_subscriptions("name=na%20me&email=na_me%40example.com")
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn removed_suppressions_receive_a_confirmation_email_again() {
+    // init
+    let app = spawn_app().await;
+    app.post_admin_suppressions(&serde_json::json!({
+        "email": "na_me@example.com",
+        "reason": "legal request",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    app.delete_admin_suppression("na_me@example.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    let response = app
+        .post_subscriptions("name=na%20me&email=na_me%40example.com")
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_suppressed_subscribers() {
+    // init
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_admin_suppressions(&serde_json::json!({
+        "email": "na_me@example.com",
+        "reason": "legal request",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>"
+            }
+        }))
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn bounced_addresses_are_added_to_the_suppression_list() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    app.post_postmark_webhook(&serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": "HardBounce",
+        "Email": "na_me@example.com",
+        "Inactive": true,
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    // assert
+    let body: serde_json::Value = app.get_admin_suppressions().await.json().await.unwrap();
+    assert_eq!(body[0]["email"], "na_me@example.com");
+    assert_eq!(body[0]["reason"], "hard_bounce");
+    assert_eq!(body[0]["source"], "postmark");
+}
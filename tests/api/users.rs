@@ -0,0 +1,254 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, spawn_app_with, TestApp};
+use newsletter::configuration::BasicAuthSettings;
+use reqwest::Method;
+use secrecy::ExposeSecret;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Sends a request to an admin route as the given user
+async fn request_as(
+    app: &TestApp,
+    user: &BasicAuthSettings,
+    method: Method,
+    route: &str,
+    body: Option<serde_json::Value>,
+) -> reqwest::Response {
+    let mut request = app
+        .http_client
+        .request(method, format!("{}/api/v1/admin/{}", app.address, route))
+        .basic_auth(&user.username, Some(user.password.expose_secret()));
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    request.send().await.expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn created_users_are_listed_without_their_password() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .post_admin_users(&serde_json::json!({
+            "username": "grace",
+            "password": "a long enough password",
+            "role": "editor",
+        }))
+        .await;
+
+    // assert
+    assert_eq!(201, response.status().as_u16());
+    let listed: serde_json::Value = request_as(&app, &app.admin, Method::GET, "users", None)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed[0]["username"], "grace");
+    assert_eq!(listed[0]["role"], "editor");
+    assert!(listed[0].get("password").is_none());
+    assert!(listed[0].get("password_hash").is_none());
+}
+
+#[tokio::test]
+async fn each_role_is_only_granted_its_own_routes() {
+    // init
+    let app = spawn_app().await;
+    let test_cases = vec![
+        ("analyst", "email_log", "suppressions"),
+        ("support", "suppressions", "email_log"),
+        ("support", "subscribers", "users"),
+    ];
+
+    for (role, allowed, forbidden) in test_cases {
+        let user = app.create_admin_user(role).await;
+
+        // execute
+        let allowed_response = request_as(&app, &user, Method::GET, allowed, None).await;
+        let forbidden_response = request_as(&app, &user, Method::GET, forbidden, None).await;
+
+        // assert
+        assert_eq!(
+            200,
+            allowed_response.status().as_u16(),
+            "The {} role was denied {}",
+            role,
+            allowed
+        );
+        assert_eq!(
+            403,
+            forbidden_response.status().as_u16(),
+            "The {} role was granted {}",
+            role,
+            forbidden
+        );
+        let body: serde_json::Value = forbidden_response.json().await.unwrap();
+        assert_eq!(body["code"], "auth.forbidden");
+    }
+}
+
+#[tokio::test]
+async fn editors_publish_newsletters_with_their_own_credentials() {
+    // init
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {"ErrorCode": 0, "Message": "OK", "To": "na_me@example.com"}
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let editor = app.create_admin_user("editor").await;
+    let analyst = app.create_admin_user("analyst").await;
+    let issue = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {"text": "Body", "html": "<p>Body</p>"},
+    });
+
+    // execute
+    let published = request_as(
+        &app,
+        &editor,
+        Method::POST,
+        "newsletters",
+        Some(issue.clone()),
+    )
+    .await;
+    let denied = request_as(&app, &analyst, Method::POST, "newsletters", Some(issue)).await;
+
+    // assert
+    assert_eq!(200, published.status().as_u16());
+    assert_eq!(403, denied.status().as_u16());
+}
+
+#[tokio::test]
+async fn invalid_or_deleted_users_are_rejected_with_a_401() {
+    // init
+    let app = spawn_app().await;
+    let user = app.create_admin_user("analyst").await;
+    let wrong_password = BasicAuthSettings {
+        username: user.username.clone(),
+        password: "not the password".into(),
+    };
+    let listed: serde_json::Value = request_as(&app, &app.admin, Method::GET, "users", None)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let user_id = listed[0]["user_id"].as_str().unwrap();
+
+    // execute
+    let with_wrong_password =
+        request_as(&app, &wrong_password, Method::GET, "email_log", None).await;
+    let deleted = request_as(
+        &app,
+        &app.admin,
+        Method::DELETE,
+        &format!("users/{}", user_id),
+        None,
+    )
+    .await;
+    let once_deleted = request_as(&app, &user, Method::GET, "email_log", None).await;
+
+    // assert
+    assert_eq!(401, with_wrong_password.status().as_u16());
+    assert_eq!(200, deleted.status().as_u16());
+    assert_eq!(401, once_deleted.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="admin""#,
+        once_deleted.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn creating_a_user_returns_400_for_invalid_data() {
+    // init
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"username": " ", "password": "a long enough password", "role": "editor"}),
+            "user.username_empty",
+            "empty username",
+        ),
+        (
+            serde_json::json!({"username": "grace", "password": "short", "role": "editor"}),
+            "user.password_too_short",
+            "short password",
+        ),
+        (
+            serde_json::json!({"username": "grace", "password": "a long enough password", "role": "admin"}),
+            "user.role_unknown",
+            "unknown role",
+        ),
+    ];
+
+    for (body, code, description) in test_cases {
+        // execute
+        let response = app.post_admin_users(&body).await;
+
+        // assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not fail with 400 Bad Request when the payload had {}.",
+            description
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["errors"][0]["code"], code);
+    }
+}
+
+#[tokio::test]
+async fn deleting_a_user_returns_a_problem_document_for_a_malformed_id() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = request_as(&app, &app.admin, Method::DELETE, "users/not-a-uuid", None).await;
+
+    // assert
+    assert_eq!(400, response.status().as_u16());
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "request.malformed");
+}
+
+#[tokio::test]
+async fn a_taken_username_is_rejected_with_a_409() {
+    // init
+    let app = spawn_app().await;
+    let user = serde_json::json!({
+        "username": "grace",
+        "password": "a long enough password",
+        "role": "support",
+    });
+    app.post_admin_users(&user)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // execute
+    let response = app.post_admin_users(&user).await;
+
+    // assert
+    assert_eq!(409, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "user.username_taken");
+}
+
+#[tokio::test]
+async fn the_bootstrap_owner_is_disabled_unless_configured() {
+    // init
+    let app = spawn_app_with(|c| c.admin = None).await;
+
+    // execute
+    let response = request_as(&app, &app.admin, Method::GET, "users", None).await;
+
+    // assert
+    assert_eq!(401, response.status().as_u16());
+}
@@ -0,0 +1,62 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn versioned_routes_are_not_deprecated() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app.get_subscriptions_confirm(Some("unknown")).await;
+
+    // assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response.headers().get("Deprecation").is_none());
+    assert!(response.headers().get("Sunset").is_none());
+}
+
+#[tokio::test]
+async fn legacy_routes_are_deprecated_aliases_of_v1() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .http_client
+        .get(format!(
+            "{}/subscriptions/confirm?subscription_token=unknown",
+            app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(response.status().as_u16(), 401);
+    let headers = response.headers();
+    assert_eq!(headers["Deprecation"], "@1746316800");
+    assert_eq!(headers["Sunset"], "Wed, 30 Jun 2027 00:00:00 GMT");
+    assert_eq!(
+        headers["Link"],
+        r#"</api/v1/subscriptions/confirm>; rel="successor-version""#
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "token.unknown");
+}
+
+#[tokio::test]
+async fn legacy_admin_routes_still_require_credentials() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .http_client
+        .get(format!("{}/admin/suppressions", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response.headers().get("Deprecation").is_some());
+}
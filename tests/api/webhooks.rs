@@ -0,0 +1,187 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, spawn_app_with};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+fn hard_bounce(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": "HardBounce",
+        "TypeCode": 1,
+        "Email": email,
+        "Inactive": true,
+    })
+}
+
+#[tokio::test]
+async fn requests_missing_authorization_are_rejected() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .http_client
+        .post(format!("{}/api/v1/webhooks/postmark", app.address))
+        .json(&hard_bounce("na_me@example.com"))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="webhooks""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn requests_with_invalid_password_are_rejected() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .http_client
+        .post(format!("{}/api/v1/webhooks/postmark", app.address))
+        .basic_auth(&app.webhooks.username, Some("wrong-password"))
+        .json(&hard_bounce("na_me@example.com"))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // assert
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn webhooks_are_disabled_unless_credentials_are_configured() {
+    // init
+    let app = spawn_app_with(|c| c.webhooks = None).await;
+
+    // execute
+    let response = app
+        .post_postmark_webhook(&hard_bounce("na_me@example.com"))
+        .await;
+
+    // assert
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn a_hard_bounce_marks_the_subscriber_as_bounced() {
+    // init
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // execute
+    let response = app
+        .post_postmark_webhook(&hard_bounce("na_me@example.com"))
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.status, "bounced");
+}
+
+#[tokio::test]
+async fn a_soft_bounce_leaves_the_subscriber_untouched() {
+    // init
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // execute
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "SoftBounce",
+            "TypeCode": 4096,
+            "Email": "na_me@example.com",
+            "Inactive": false,
+        }))
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn a_spam_complaint_marks_the_subscriber_as_complained() {
+    // init
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // execute
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "Type": "SpamComplaint",
+            "TypeCode": 512,
+            "Email": "na_me@example.com",
+        }))
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.status, "complained");
+}
+
+#[tokio::test]
+async fn unsupported_record_types_are_acknowledged() {
+    // init
+    let app = spawn_app().await;
+
+    // execute
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "Delivery",
+            "Recipient": "na_me@example.com",
+        }))
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_bounced_subscribers() {
+    // init
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.post_postmark_webhook(&hard_bounce("na_me@example.com"))
+        .await
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // execute
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "text": "Newsletter body as plain text",
+                "html": "<p>Newsletter body as HTML</p>"
+            }
+        }))
+        .await;
+
+    // assert
+    assert_eq!(200, response.status().as_u16());
+}